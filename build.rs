@@ -1,3 +1,183 @@
+use std::{env, fs, path::Path};
+
+// The MUD world parser only needs `std`, so the build runs it on the host.
+#[allow(dead_code)]
+#[path = "src/apps/mud_game_app/world.rs"]
+mod mud_world;
+
+const BOARDS_FILE: &str = "boards.toml";
+const MUD_WORLD_FILES: &[&str] = &[
+    "src/apps/mud_game_app/world.rs",
+    "src/apps/mud_game_app/crypt.txt",
+];
+
 fn main() {
     embuild::espidf::sysenv::output();
+    generate_board_profile();
+    check_mud_world();
+}
+
+/// Reads `boards.toml`, picks the selected profile and writes it to
+/// `$OUT_DIR/board_profile.rs` as a typed `BoardProfile` constant.
+fn generate_board_profile() {
+    println!("cargo:rerun-if-changed={}", BOARDS_FILE);
+    println!("cargo:rerun-if-env-changed=BOARD");
+
+    let contents = fs::read_to_string(BOARDS_FILE)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", BOARDS_FILE, e));
+    let config: toml::Table = contents
+        .parse()
+        .unwrap_or_else(|e| panic!("failed to parse {}: {}", BOARDS_FILE, e));
+    let boards = config
+        .get("boards")
+        .and_then(toml::Value::as_table)
+        .unwrap_or_else(|| panic!("{} has no [boards] table", BOARDS_FILE));
+
+    let id = select_board(&config, boards);
+    let board = boards[&id]
+        .as_table()
+        .unwrap_or_else(|| panic!("[boards.{}] is not a table", id));
+
+    let display = match board.get("display").and_then(toml::Value::as_table) {
+        Some(display) => format!(
+            "Some(DisplayProfile {{
+        sclk: {},
+        mosi: {},
+        miso: {},
+        cs: {},
+        dc: {},
+        rst: {},
+        backlight: {},
+        width: {},
+        height: {},
+        orientation: DisplayOrientation::{},
+        spi_baudrate_hz: {},
+        dma_buffer_size: {},
+    }})",
+            int(&id, display, "sclk"),
+            int(&id, display, "mosi"),
+            opt_int(&id, display, "miso"),
+            int(&id, display, "cs"),
+            int(&id, display, "dc"),
+            opt_int(&id, display, "rst"),
+            int(&id, display, "backlight"),
+            int(&id, display, "width"),
+            int(&id, display, "height"),
+            orientation(&id, display),
+            int(&id, display, "spi_baudrate_hz"),
+            opt_int(&id, display, "dma_buffer_size"),
+        ),
+        None => "None".to_string(),
+    };
+    let rgb_led = match board.get("rgb_led").and_then(toml::Value::as_table) {
+        Some(led) => format!(
+            "Some(RgbLedProfile {{ red: {}, green: {}, blue: {} }})",
+            int(&id, led, "red"),
+            int(&id, led, "green"),
+            int(&id, led, "blue"),
+        ),
+        None => "None".to_string(),
+    };
+    let name = board
+        .get("name")
+        .and_then(toml::Value::as_str)
+        .unwrap_or(&id);
+
+    let code = format!(
+        "// Generated by build.rs from {file}. Do not edit.
+
+/// Profile the firmware is built for (`{id}` in {file}).
+pub const BOARD: BoardProfile = BoardProfile {{
+    id: {id:?},
+    name: {name:?},
+    status_led: {status_led},
+    rgb_led: {rgb_led},
+    display: {display},
+}};
+",
+        file = BOARDS_FILE,
+        status_led = opt_int(&id, board, "status_led"),
+    );
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("board_profile.rs");
+    fs::write(&out, code).unwrap_or_else(|e| panic!("failed to write {}: {}", out.display(), e));
+}
+
+/// Selection order: `board-*` cargo feature, then `BOARD` env var, then `default`.
+fn select_board(config: &toml::Table, boards: &toml::Table) -> String {
+    let from_features: Vec<&String> = boards
+        .keys()
+        .filter(|id| {
+            let feature = format!(
+                "CARGO_FEATURE_BOARD_{}",
+                id.to_uppercase().replace('-', "_")
+            );
+            env::var_os(feature).is_some()
+        })
+        .collect();
+    if from_features.len() > 1 {
+        println!(
+            "cargo:warning=multiple board features enabled ({:?}), using {}",
+            from_features, from_features[0]
+        );
+    }
+
+    let id = from_features
+        .first()
+        .map(|id| id.to_string())
+        .or_else(|| env::var("BOARD").ok())
+        .or_else(|| {
+            config
+                .get("default")
+                .and_then(toml::Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| panic!("no board selected and no default in {}", BOARDS_FILE));
+
+    if !boards.contains_key(&id) {
+        panic!(
+            "unknown board '{}', expected one of {:?}",
+            id,
+            boards.keys().collect::<Vec<_>>()
+        );
+    }
+    id
+}
+
+fn int(id: &str, table: &toml::Table, key: &str) -> i64 {
+    table
+        .get(key)
+        .and_then(toml::Value::as_integer)
+        .unwrap_or_else(|| panic!("board '{}' is missing integer '{}'", id, key))
+}
+
+fn opt_int(id: &str, table: &toml::Table, key: &str) -> String {
+    match table.get(key) {
+        None => "None".to_string(),
+        Some(_) => format!("Some({})", int(id, table, key)),
+    }
+}
+
+fn orientation(id: &str, display: &toml::Table) -> &'static str {
+    match display.get("orientation").and_then(toml::Value::as_str) {
+        None | Some("portrait") => "Portrait",
+        Some("landscape") => "Landscape",
+        Some("portrait-swapped") => "PortraitSwapped",
+        Some("landscape-swapped") => "LandscapeSwapped",
+        Some(other) => panic!("board '{}' has unknown orientation '{}'", id, other),
+    }
+}
+
+/// Fails the build if the built-in MUD world has dangling references, and passes
+/// on the parser's warnings (one-way exits, unreachable rooms).
+fn check_mud_world() {
+    for file in MUD_WORLD_FILES {
+        println!("cargo:rerun-if-changed={}", file);
+    }
+
+    let world = mud_world::World::parse(mud_world::BUILTIN)
+        .unwrap_or_else(|e| panic!("invalid built-in MUD world ({}): {}", MUD_WORLD_FILES[1], e));
+    for warning in world.warnings() {
+        println!("cargo:warning=MUD world: {}", warning);
+    }
 }
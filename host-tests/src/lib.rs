@@ -0,0 +1,40 @@
+//! # host-tests
+//! Builds the firmware modules that only need `std` for the host, so their unit
+//! tests run with `cargo test` in this directory.
+//!
+//! The sources are included from `../src` with `#[path]`, the way `build.rs`
+//! includes the MUD world parser, and the tests sit next to the code in
+//! `#[cfg(test)]` modules. The firmware itself only builds for the ESP32; code
+//! that calls into ESP-IDF is left out with `#[cfg(target_os = "espidf")]`.
+//!
+
+#[path = "../../src/apps/mud_game_app"]
+pub mod mud_game_app {
+    pub mod combat;
+    pub mod engine;
+    pub mod game;
+    pub mod parser;
+    pub mod render;
+    pub mod save;
+    pub mod server;
+    pub mod world;
+}
+
+#[path = "../../src/shell/mod.rs"]
+pub mod shell;
+
+/// Stand-in for the firmware's `storage` module, which mounts SPIFFS through
+/// ESP-IDF: the same mount point, but no flash to report on.
+pub mod storage {
+    pub const FS_MOUNT_POINT: &str = "/spiffs";
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Usage {
+        pub total: usize,
+        pub used: usize,
+    }
+
+    pub fn usage() -> anyhow::Result<Usage> {
+        anyhow::bail!("no flash on the host")
+    }
+}
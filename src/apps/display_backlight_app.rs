@@ -4,7 +4,6 @@
 //! Controls a display’s backlight on an ESP32.
 //!  
 
-use esp_idf_svc::log::EspLogger;
 use esp_idf_sys as _; // pull in ESP-IDF
 
 // use esp_idf_hal::prelude::*;
@@ -21,14 +20,33 @@ use esp_idf_hal::adc::oneshot::config::AdcChannelConfig;
 use esp_idf_hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
 
 // GPIO
-use esp_idf_hal::gpio::PinDriver;
+use esp_idf_hal::gpio::{AnyOutputPin, PinDriver};
 
 use std::{thread, time::Duration};
 
+use super::App;
+use crate::board::BOARD;
+
+/// Registry entry for [`run`].
+pub struct DisplayBacklightApp;
+
+impl App for DisplayBacklightApp {
+    fn name(&self) -> &'static str {
+        "display_backlight_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Backlight, RGB LED and light sensor demo"
+    }
+
+    fn run(&self) -> anyhow::Result<()> {
+        run()
+    }
+}
+
 /// Runs the display backlight demo:  
 /// initializes the SPI, configures the display, and toggles the backlight.
 pub fn run() -> anyhow::Result<()> {
-    EspLogger::initialize_default();
     let mut peripherals = Peripherals::take().unwrap();
 
     // —————————————————
@@ -46,15 +64,29 @@ pub fn run() -> anyhow::Result<()> {
     // —————————————————
     // Backlight & RGB LEDs
     // —————————————————
-    let mut backlight = PinDriver::output(peripherals.pins.gpio21)?;
-    backlight.set_high()?; // turn the backlight on
-
-    let mut red = PinDriver::output(peripherals.pins.gpio4)?;
-    let mut green = PinDriver::output(peripherals.pins.gpio16)?;
-    let mut blue = PinDriver::output(peripherals.pins.gpio17)?;
-    red.set_low()?; // LEDs start off
-    green.set_low()?;
-    blue.set_low()?;
+    // Pins come from the board profile; boards without them skip that part.
+    // SAFETY: these GPIOs are only driven here.
+    let _backlight = match BOARD.display {
+        Some(display) => {
+            let mut backlight = PinDriver::output(unsafe { AnyOutputPin::new(display.backlight) })?;
+            backlight.set_high()?; // turn the backlight on
+            Some(backlight)
+        }
+        None => None,
+    };
+
+    let _rgb = match BOARD.rgb_led {
+        Some(led) => {
+            let mut red = PinDriver::output(unsafe { AnyOutputPin::new(led.red) })?;
+            let mut green = PinDriver::output(unsafe { AnyOutputPin::new(led.green) })?;
+            let mut blue = PinDriver::output(unsafe { AnyOutputPin::new(led.blue) })?;
+            red.set_low()?; // LEDs start off
+            green.set_low()?;
+            blue.set_low()?;
+            Some((red, green, blue))
+        }
+        None => None,
+    };
 
     // —————————————————
     // Touch controller on SPI3 (VSPI)
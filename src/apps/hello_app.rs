@@ -4,14 +4,30 @@
 
 use esp_idf_sys as _; // ...
 
+use super::App;
+
+/// Registry entry for [`run`].
+pub struct HelloApp;
+
+impl App for HelloApp {
+    fn name(&self) -> &'static str {
+        "hello_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Prints hello five times then halts"
+    }
+
+    fn run(&self) -> anyhow::Result<()> {
+        run();
+        Ok(())
+    }
+}
+
 /// Runs the hello_app:  
-/// 1. Initializes the logger  
-/// 2. Prints “hello” five times  
-/// 3. Signals completion and halts.
+/// 1. Prints “hello” five times  
+/// 2. Signals completion and halts.
 pub fn run() {
-    // Initialize the logger so println! outputs appear on the serial console
-    esp_idf_svc::log::EspLogger::initialize_default();
-
     // Print "hello" five times
     for _ in 0..5 {
         println!("hello");
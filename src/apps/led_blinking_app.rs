@@ -1,5 +1,5 @@
 //! # led_blinking_app
-//! Blinks an LED on the ESP32 using esp-idf-hal v0.45.2.
+//! Blinks the board's status LED using esp-idf-hal v0.45.2.
 //!  
 
 // Link the ESP-IDF C runtime and apply necessary patches (via binstart feature).
@@ -7,30 +7,43 @@ use esp_idf_sys as _;
 
 // --- Required Imports ---
 use esp_idf_hal::delay::FreeRtos; // FreeRTOS-based delay
-use esp_idf_hal::gpio::PinDriver; // GPIO PinDriver for configuration
-use esp_idf_hal::peripherals::Peripherals; // Access to chip peripherals
+use esp_idf_hal::gpio::{AnyOutputPin, PinDriver}; // GPIO PinDriver for configuration
 
-use anyhow::Result;
-use esp_idf_svc::log::EspLogger; // ESP-IDF logger implementation
+use anyhow::{anyhow, Result};
 use log::info; // Logging facade // <-- Import anyhow::Result for error handling
 
+use super::App;
+use crate::board::BOARD;
+
+/// Registry entry for [`run`].
+pub struct LedBlinkingApp;
+
+impl App for LedBlinkingApp {
+    fn name(&self) -> &'static str {
+        "led_blinking_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Blinks the status LED once per second"
+    }
+
+    fn run(&self) -> Result<()> {
+        run()
+    }
+}
+
 /// Runs the LED blinking loop:  
 /// toggles the LED on/off every second and logs the state.
 pub fn run() -> Result<()> {
     // <-- Use anyhow::Result here
-    // Initialize the ESP-IDF logger. Outputs to serial console.
-    EspLogger::initialize_default();
-    info!("Logger initialized");
-
-    // Take ownership of ESP32 peripherals.
-    let peripherals = Peripherals::take()?;
-    info!("Peripherals taken");
-
     // --- GPIO Configuration (using PinDriver) ---
-    // IMPORTANT: Change `peripherals.pins.gpio4` if your LED is on a different pin!
-    info!("Configuring GPIO for LED...");
-    // *** USE THIS LINE FOR v0.45.2 ***
-    let mut led_pin = PinDriver::output(peripherals.pins.gpio4)?;
+    // The LED pin comes from the board profile (`status_led` in boards.toml).
+    let pin = BOARD
+        .status_led
+        .ok_or_else(|| anyhow!("board {} has no status LED", BOARD.name))?;
+    info!("Configuring GPIO{} for LED...", pin);
+    // SAFETY: nothing else in this app drives the status LED pin.
+    let mut led_pin = PinDriver::output(unsafe { AnyOutputPin::new(pin) })?;
     info!("GPIO configuration complete. Starting blink loop...");
 
     // --- Main Application Loop ---
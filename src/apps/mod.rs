@@ -1,3 +1,7 @@
+//! # apps
+//! Demo applications and the registry used by the boot-time selector.
+//!
+
 pub mod display_backlight_app;
 pub mod graphics_app;
 pub mod hello_app;
@@ -5,3 +9,47 @@ pub mod led_blinking_app;
 pub mod mud_game_app;
 pub mod rotating_cube_app;
 pub mod rtos_shell_app;
+
+/// Common interface implemented by every demo so it can be picked at boot.
+pub trait App: Sync {
+    /// Identifier used by the selector and stored in NVS (e.g. `hello_app`).
+    fn name(&self) -> &'static str;
+
+    /// One-line description shown in the serial menu.
+    fn description(&self) -> &'static str;
+
+    /// Runs the app. Most apps never return.
+    fn run(&self) -> anyhow::Result<()>;
+}
+
+/// Returns every app compiled into this firmware image, in menu order.
+pub fn registry() -> Vec<&'static dyn App> {
+    #[allow(unused_mut)]
+    let mut apps: Vec<&'static dyn App> =
+        vec![&hello_app::HelloApp, &led_blinking_app::LedBlinkingApp];
+
+    #[cfg(feature = "display-support")]
+    apps.push(&display_backlight_app::DisplayBacklightApp);
+
+    // Graphics apps are hidden on boards without a display (e.g. `board-headless`).
+    #[cfg(feature = "graphics-support")]
+    if crate::board::BOARD.display.is_some() {
+        apps.push(&graphics_app::GraphicsApp);
+        apps.push(&rotating_cube_app::RotatingCubeApp);
+        apps.push(&rtos_shell_app::RtosShellApp);
+    }
+
+    // The MUD falls back to the console without a display.
+    #[cfg(feature = "graphics-support")]
+    {
+        apps.push(&mud_game_app::MudGameApp);
+        apps.push(&mud_game_app::MudServerApp);
+    }
+
+    apps
+}
+
+/// Looks up an app by its [`App::name`].
+pub fn find(name: &str) -> Option<&'static dyn App> {
+    registry().into_iter().find(|app| app.name() == name)
+}
@@ -0,0 +1,218 @@
+//! # mud_game_app::combat
+//! Turn-based combat rules and the random number generator behind them.
+//!
+//! [`Rng`] is seeded explicitly, so a fight replays the same way for the same
+//! seed; the app seeds it from the hardware RNG at startup.
+//!
+
+/// Small, fast PRNG (xorshift64*). Not for anything security-related.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // An all-zero state would stay zero forever.
+        Self {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+    }
+
+    /// Uniform in `low..=high`.
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        if high <= low {
+            return low;
+        }
+        let span = (high - low) as u32 + 1;
+        low + (self.next_u32() % span) as i32
+    }
+
+    /// True with the given chance in percent.
+    pub fn chance(&mut self, percent: i32) -> bool {
+        self.range(0, 99) < percent
+    }
+}
+
+/// What matters about a fighter for one blow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub attack: i32,
+    pub defense: i32,
+}
+
+/// Result of one attack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blow {
+    Miss,
+    Hit { damage: i32, critical: bool },
+}
+
+const BASE_HIT_CHANCE: i32 = 75;
+/// Hit chance gained per point of attack over the defender's defense.
+const HIT_CHANCE_PER_POINT: i32 = 5;
+const MIN_HIT_CHANCE: i32 = 25;
+const MAX_HIT_CHANCE: i32 = 95;
+const CRITICAL_CHANCE: i32 = 5;
+
+/// Resolves one attack. A hit always does at least 1 damage; criticals double it.
+pub fn strike(rng: &mut Rng, attacker: Stats, defender: Stats) -> Blow {
+    let hit_chance = (BASE_HIT_CHANCE
+        + HIT_CHANCE_PER_POINT * (attacker.attack - defender.defense))
+        .clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE);
+    if !rng.chance(hit_chance) {
+        return Blow::Miss;
+    }
+    let roll = rng.range(0, attacker.attack / 2);
+    let damage = (attacker.attack + roll - defender.defense).max(1);
+    let critical = rng.chance(CRITICAL_CHANCE);
+    Blow::Hit {
+        damage: if critical { damage * 2 } else { damage },
+        critical,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVEN: Stats = Stats {
+        attack: 5,
+        defense: 2,
+    };
+
+    fn strikes(seed: u64, attacker: Stats, defender: Stats, count: usize) -> Vec<Blow> {
+        let mut rng = Rng::new(seed);
+        (0..count)
+            .map(|_| strike(&mut rng, attacker, defender))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn zero_seed_still_varies() {
+        let mut rng = Rng::new(0);
+        let first = rng.next_u32();
+        assert!((0..10).any(|_| rng.next_u32() != first));
+    }
+
+    #[test]
+    fn range_stays_in_bounds_and_covers_them() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            let value = rng.range(-3, 3);
+            assert!((-3..=3).contains(&value), "{} out of range", value);
+            seen[(value + 3) as usize] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+    }
+
+    #[test]
+    fn empty_range_returns_low() {
+        let mut rng = Rng::new(7);
+        assert_eq!(rng.range(4, 4), 4);
+        assert_eq!(rng.range(4, 1), 4);
+    }
+
+    #[test]
+    fn strike_both_hits_and_misses() {
+        let blows = strikes(1, EVEN, EVEN, 200);
+        assert!(blows.contains(&Blow::Miss));
+        assert!(blows.iter().any(|blow| matches!(blow, Blow::Hit { .. })));
+    }
+
+    #[test]
+    fn hit_damage_is_attack_plus_roll_minus_defense() {
+        // attack 5 rolls 0..=2 extra, minus defense 2
+        for blow in strikes(2, EVEN, EVEN, 500) {
+            match blow {
+                Blow::Hit {
+                    damage,
+                    critical: false,
+                } => assert!((3..=5).contains(&damage), "damage {}", damage),
+                Blow::Hit {
+                    damage,
+                    critical: true,
+                } => assert!((6..=10).contains(&damage), "critical damage {}", damage),
+                Blow::Miss => {}
+            }
+        }
+    }
+
+    #[test]
+    fn hit_does_at_least_one_damage() {
+        let weak = Stats {
+            attack: 1,
+            defense: 0,
+        };
+        let armored = Stats {
+            attack: 0,
+            defense: 50,
+        };
+        let hits: Vec<(i32, bool)> = strikes(3, weak, armored, 500)
+            .into_iter()
+            .filter_map(|blow| match blow {
+                Blow::Hit { damage, critical } => Some((damage, critical)),
+                Blow::Miss => None,
+            })
+            .collect();
+        assert!(!hits.is_empty(), "the minimum hit chance should still land");
+        for (damage, critical) in hits {
+            assert_eq!(damage, if critical { 2 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn criticals_happen_and_double_damage() {
+        let blows = strikes(4, EVEN, EVEN, 2000);
+        let criticals = blows
+            .iter()
+            .filter(|blow| matches!(blow, Blow::Hit { critical: true, .. }))
+            .count();
+        assert!(criticals > 0);
+        assert!(criticals < blows.len() / 5, "{} criticals", criticals);
+        for blow in blows {
+            if let Blow::Hit {
+                damage,
+                critical: true,
+            } = blow
+            {
+                assert_eq!(damage % 2, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn hit_chance_is_clamped() {
+        let strong = Stats {
+            attack: 100,
+            defense: 0,
+        };
+        let misses = strikes(5, strong, EVEN, 1000)
+            .into_iter()
+            .filter(|blow| *blow == Blow::Miss)
+            .count();
+        // 95% at most, so some still miss
+        assert!(misses > 0 && misses < 150, "{} misses", misses);
+    }
+}
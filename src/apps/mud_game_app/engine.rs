@@ -0,0 +1,197 @@
+//! # mud_game_app::engine
+//! The single-player game as a deterministic step function: feed it lines with
+//! [`GameEngine::handle_command`], advance time with [`GameEngine::tick`], and
+//! hand the [`View`] it produces to a [`Renderer`](super::render::Renderer).
+//!
+//! There are no clocks or threads in here; the caller decides how long a tick is
+//! (the app uses 100 ms), so the same seed, commands and ticks always give the
+//! same game.
+//!
+
+use super::game::{GameState, PlayerId, Request};
+use super::world::World;
+
+/// Ticks per game turn (regeneration, monster respawns, wandering NPCs and timer
+/// events).
+pub const TICKS_PER_TURN: u32 = 30;
+
+/// What to show for one player, independent of how it is drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View {
+    pub health: i32,
+    pub max_health: i32,
+    pub level: i32,
+    pub xp: i32,
+    pub xp_needed: i32,
+    pub room_name: String,
+    pub room_description: String,
+    /// `You see: ...`, if there is anything to see.
+    pub contents: Option<String>,
+    pub exits: String,
+    /// The message log, oldest first.
+    pub messages: Vec<String>,
+}
+
+impl View {
+    /// What player `id` sees right now.
+    pub fn new(state: &GameState, id: PlayerId) -> Self {
+        let player = state.player(id);
+        let room = state.world.room(player.room);
+        Self {
+            health: player.health,
+            max_health: player.max_health(),
+            level: player.level,
+            xp: player.xp,
+            xp_needed: player.xp_needed(),
+            room_name: room.name.clone(),
+            room_description: room.description.clone(),
+            contents: state.contents_line(id),
+            exits: room.exits_line(),
+            messages: player.messages().cloned().collect(),
+        }
+    }
+}
+
+/// One player's game plus the clock that drives it.
+pub struct GameEngine {
+    state: GameState,
+    player: PlayerId,
+    ticks: u64,
+    turns: u64,
+}
+
+impl GameEngine {
+    /// Starts a game in `world` with one player called `name`.
+    pub fn new(world: World, seed: u64, name: &str) -> Self {
+        let mut state = GameState::new(world, seed);
+        let player = state.join(name);
+        Self {
+            state,
+            player,
+            ticks: 0,
+            turns: 0,
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut GameState {
+        &mut self.state
+    }
+
+    pub fn player(&self) -> PlayerId {
+        self.player
+    }
+
+    /// Game turns played so far.
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    /// Runs one line typed by the player; see [`GameState::handle_command`].
+    pub fn handle_command(&mut self, input: &str) -> Option<Request> {
+        self.state.handle_command(self.player, input)
+    }
+
+    /// Advances the clock by one tick. The first tick and every
+    /// [`TICKS_PER_TURN`]th after it play a game turn; returns whether this one did.
+    pub fn tick(&mut self) -> bool {
+        let turn = self.ticks % u64::from(TICKS_PER_TURN) == 0;
+        self.ticks += 1;
+        if turn {
+            self.state.simulate_turn();
+            self.turns += 1;
+        }
+        turn
+    }
+
+    /// Lines for the console since the last call.
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.state.take_output(self.player)
+    }
+
+    /// The current [`View`] if anything visible changed since the last call.
+    pub fn take_view(&mut self) -> Option<View> {
+        if !self.state.dirty {
+            return None;
+        }
+        self.state.dirty = false;
+        Some(View::new(&self.state, self.player))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays `commands`, one every 20 ticks, for `ticks` ticks and returns
+    /// everything the player saw.
+    fn play(seed: u64, commands: &[&str], ticks: usize) -> (Vec<String>, Vec<View>) {
+        let mut engine = GameEngine::new(World::builtin(), seed, "Ann");
+        let mut output = Vec::new();
+        let mut views = Vec::new();
+        for tick in 0..ticks {
+            if tick % 20 == 5 {
+                if let Some(command) = commands.get(tick / 20) {
+                    engine.handle_command(command);
+                }
+            }
+            engine.tick();
+            output.extend(engine.take_output());
+            views.extend(engine.take_view());
+        }
+        (output, views)
+    }
+
+    #[test]
+    fn same_seed_commands_and_ticks_replay_identically() {
+        let commands = [
+            "n",
+            "take sword",
+            "wield sword",
+            "kill rat",
+            "kill rat",
+            "kill rat",
+            "kill rat",
+            "s",
+            "w",
+            "talk ghost",
+            "1",
+        ];
+        let first = play(9, &commands, 400);
+        assert_eq!(first, play(9, &commands, 400));
+        assert!(first
+            .0
+            .iter()
+            .any(|line| line.starts_with("You hit the giant rat")));
+    }
+
+    #[test]
+    fn tick_plays_a_turn_first_and_then_every_ticks_per_turn() {
+        let mut engine = GameEngine::new(World::builtin(), 1, "Ann");
+        let ticks = 3 * TICKS_PER_TURN + 1;
+        for tick in 0..ticks {
+            assert_eq!(engine.tick(), tick % TICKS_PER_TURN == 0, "tick {}", tick);
+        }
+        assert_eq!(engine.turns(), 4);
+    }
+
+    #[test]
+    fn take_view_only_when_something_changed() {
+        let mut engine = GameEngine::new(World::builtin(), 1, "Ann");
+        let view = engine.take_view().expect("a new game needs drawing");
+        assert_eq!(view.room_name, "Crypt Entrance");
+        assert_eq!(engine.take_view(), None);
+
+        // full health, so a turn changes nothing visible
+        engine.tick();
+        assert_eq!(engine.take_view(), None);
+
+        engine.handle_command("n");
+        let view = engine.take_view().expect("moving changes the room");
+        assert_eq!(view.room_name, "Hall of Bones");
+        assert_eq!(engine.take_view(), None);
+    }
+}
@@ -0,0 +1,1227 @@
+//! # mud_game_app::game
+//! Game rules: players, movement, items and equipment, combat, experience,
+//! NPCs and their conversations, and room events, driven by parsed [`Command`]s.
+//!
+//! Any number of players share one world, each with their own stats, inventory
+//! and message log; they see each other come and go, fight and talk. Nothing here
+//! touches the display, the console, sockets or ESP-IDF: replies are queued per
+//! player for the caller to deliver, and randomness comes from a seeded [`Rng`],
+//! so the same seed and commands always play out the same way on the host.
+//!
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::combat::{self, Blow, Rng, Stats};
+use super::parser::{self, Command};
+use super::world::{find_name, Direction, ItemId, MonsterId, RoomId, Slot, Trigger, World};
+
+/// Messages kept for each player's message log.
+pub const MAX_MESSAGES: usize = 5;
+
+const BASE_HEALTH: i32 = 90;
+const HEALTH_PER_LEVEL: i32 = 10;
+const BASE_ATTACK: i32 = 3;
+const BASE_DEFENSE: i32 = 1;
+/// Experience needed for the next level is this times the current level.
+const XP_PER_LEVEL: i32 = 50;
+const REGEN_PER_TURN: i32 = 1;
+
+/// Handed out by [`GameState::join`]; never reused within a game.
+pub type PlayerId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    pub name: String,
+    pub health: i32,
+    pub level: i32,
+    /// Experience gained towards the next level.
+    pub xp: i32,
+    pub weapon: Option<ItemId>,
+    pub armor: Option<ItemId>,
+    pub room: RoomId,
+    pub inventory: Vec<ItemId>,
+    /// Per [`World::events`]: whether it has fired for this player, so `once`
+    /// events stay quiet after.
+    pub fired: Vec<bool>,
+    /// The NPC (index in [`World::npcs`]) and topic of the conversation in progress.
+    talking: Option<(usize, usize)>,
+    /// Latest messages for the display, oldest first.
+    messages: VecDeque<String>,
+    /// Everything to send this player since the last [`GameState::take_output`].
+    output: Vec<String>,
+}
+
+impl Player {
+    fn new(name: &str, world: &World) -> Self {
+        let mut player = Self {
+            name: name.to_string(),
+            health: 0,
+            level: 1,
+            xp: 0,
+            weapon: None,
+            armor: None,
+            room: world.start,
+            inventory: Vec::new(),
+            fired: vec![false; world.events.len()],
+            talking: None,
+            messages: VecDeque::new(),
+            output: Vec::new(),
+        };
+        player.health = player.max_health();
+        player
+    }
+
+    pub fn max_health(&self) -> i32 {
+        BASE_HEALTH + HEALTH_PER_LEVEL * self.level
+    }
+
+    /// Experience needed to reach the next level.
+    pub fn xp_needed(&self) -> i32 {
+        XP_PER_LEVEL * self.level
+    }
+
+    /// The message log, oldest first.
+    pub fn messages(&self) -> impl Iterator<Item = &String> {
+        self.messages.iter()
+    }
+
+    fn is_equipped(&self, item: ItemId) -> bool {
+        self.weapon == Some(item) || self.armor == Some(item)
+    }
+
+    fn unequip(&mut self, item: ItemId) {
+        if self.weapon == Some(item) {
+            self.weapon = None;
+        }
+        if self.armor == Some(item) {
+            self.armor = None;
+        }
+    }
+
+    fn add_message(&mut self, msg: String) {
+        self.output.push(msg.clone());
+        self.messages.push_back(msg);
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+}
+
+/// Per-game state of a [`World::monsters`] entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonsterState {
+    /// 0 while dead.
+    pub health: i32,
+    /// Turns until a dead monster comes back; 0 if it won't.
+    pub respawn_in: u32,
+}
+
+impl MonsterState {
+    pub fn alive(&self) -> bool {
+        self.health > 0
+    }
+}
+
+/// Something a command needs from the app, which owns the flash filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Save,
+    Load,
+}
+
+pub struct GameState {
+    pub world: World,
+    pub monsters: Vec<MonsterState>,
+    /// Where each of [`World::npcs`] is now.
+    pub npc_rooms: Vec<RoomId>,
+    players: BTreeMap<PlayerId, Player>,
+    next_player: PlayerId,
+    rng: Rng,
+    /// Turns played, for timer events and wandering NPCs.
+    turn: u64,
+    /// Set whenever something visible changed; cleared by the display loop.
+    pub dirty: bool,
+}
+
+impl GameState {
+    /// A game without players; see [`GameState::join`].
+    pub fn new(world: World, seed: u64) -> Self {
+        let monsters = world
+            .monsters
+            .iter()
+            .map(|monster| MonsterState {
+                health: monster.health,
+                respawn_in: 0,
+            })
+            .collect();
+        let npc_rooms = world.npcs.iter().map(|npc| npc.room).collect();
+        Self {
+            world,
+            monsters,
+            npc_rooms,
+            players: BTreeMap::new(),
+            next_player: 0,
+            rng: Rng::new(seed),
+            turn: 0,
+            dirty: true,
+        }
+    }
+
+    /// Adds a player at the start room and tells everyone else.
+    pub fn join(&mut self, name: &str) -> PlayerId {
+        let id = self.next_player;
+        self.next_player += 1;
+        let mut player = Player::new(name, &self.world);
+        for line in &self.world.intro {
+            player.add_message(line.clone());
+        }
+        let start = player.room;
+        self.players.insert(id, player);
+        self.tell_all(id, format!("{} has joined the game.", name));
+        self.enter_room(id, start);
+        id
+    }
+
+    /// Removes a player. What they carried is left where they stood.
+    pub fn leave(&mut self, id: PlayerId) {
+        let Some(player) = self.players.remove(&id) else {
+            return;
+        };
+        self.world
+            .room_mut(player.room)
+            .items
+            .extend(player.inventory);
+        self.tell_all(id, format!("{} has left the game.", player.name));
+        self.dirty = true;
+    }
+
+    pub fn player(&self, id: PlayerId) -> &Player {
+        &self.players[&id]
+    }
+
+    pub fn player_mut(&mut self, id: PlayerId) -> &mut Player {
+        self.players.get_mut(&id).expect("unknown player")
+    }
+
+    /// Everyone in the game, in the order they joined.
+    pub fn players(&self) -> impl Iterator<Item = (PlayerId, &Player)> {
+        self.players.iter().map(|(id, player)| (*id, player))
+    }
+
+    /// Lines to send to `id` since the last call.
+    pub fn take_output(&mut self, id: PlayerId) -> Vec<String> {
+        std::mem::take(&mut self.player_mut(id).output)
+    }
+
+    /// Adds `msg` to the player's message log and output.
+    pub fn add_message(&mut self, id: PlayerId, msg: impl Into<String>) {
+        self.player_mut(id).add_message(msg.into());
+        self.dirty = true;
+    }
+
+    /// Output only, for things the display already shows elsewhere.
+    fn echo(&mut self, id: PlayerId, line: impl Into<String>) {
+        self.player_mut(id).output.push(line.into());
+    }
+
+    /// Tells everyone in `room` except `except`.
+    fn tell_room(&mut self, room: RoomId, except: Option<PlayerId>, msg: impl Into<String>) {
+        let msg = msg.into();
+        for (id, player) in &mut self.players {
+            if player.room == room && Some(*id) != except {
+                player.add_message(msg.clone());
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Tells every player except `except`.
+    fn tell_all(&mut self, except: PlayerId, msg: impl Into<String>) {
+        let msg = msg.into();
+        for (id, player) in &mut self.players {
+            if *id != except {
+                player.add_message(msg.clone());
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Runs one line typed by player `id`. Returns what the app still has to do
+    /// for commands that reach outside the game, like `save`.
+    pub fn handle_command(&mut self, id: PlayerId, input: &str) -> Option<Request> {
+        match parser::parse(input) {
+            Ok(Command::Look) => self.look(id),
+            Ok(Command::Examine(name)) => self.examine(id, &name),
+            Ok(Command::Go(direction)) => self.go(id, direction),
+            Ok(Command::Take(item)) => self.take_item(id, &item),
+            Ok(Command::Drop(item)) => self.drop_item(id, &item),
+            Ok(Command::Inventory) => self.show_inventory(id),
+            Ok(Command::Equip(item)) => self.equip(id, &item),
+            Ok(Command::Attack(target)) => self.attack(id, target.as_deref()),
+            Ok(Command::Use(item)) => self.use_item(id, &item),
+            Ok(Command::Stats) => self.show_stats(id),
+            Ok(Command::Say(text)) => self.say(id, &text),
+            Ok(Command::Who) => self.who(id),
+            Ok(Command::Talk(npc)) => self.talk(id, &npc),
+            Ok(Command::Answer(number)) => self.answer(id, number),
+            Ok(Command::Save) => return Some(Request::Save),
+            Ok(Command::Load) => return Some(Request::Load),
+            Ok(Command::Help) => self.help(id),
+            Err(e) => self.add_message(id, e.to_string()),
+        }
+        None
+    }
+
+    /// Advances time by one turn: players heal a little, dead monsters count
+    /// down to their return, NPCs wander and timer events fire.
+    pub fn simulate_turn(&mut self) {
+        self.turn += 1;
+        let turn = self.turn;
+        let ids: Vec<PlayerId> = self.players.keys().copied().collect();
+        for id in ids {
+            let player = self.player_mut(id);
+            if player.health < player.max_health() {
+                player.health = (player.health + REGEN_PER_TURN).min(player.max_health());
+                self.dirty = true;
+            }
+            let room = self.player(id).room;
+            self.fire_events(id, |trigger| match trigger {
+                Trigger::Timer { every, room: at } => {
+                    turn % u64::from(every) == 0 && at.unwrap_or(room) == room
+                }
+                _ => false,
+            });
+        }
+
+        for index in 0..self.monsters.len() {
+            let state = &mut self.monsters[index];
+            if state.alive() || state.respawn_in == 0 {
+                continue;
+            }
+            state.respawn_in -= 1;
+            if state.respawn_in == 0 {
+                let monster = &self.world.monsters[index];
+                state.health = monster.health;
+                let (room, name) = (monster.room, monster.name.clone());
+                self.tell_room(room, None, format!("A {} appears!", name));
+            }
+        }
+
+        for npc in 0..self.world.npcs.len() {
+            let every = self.world.npcs[npc].wander;
+            if every == 0 || turn % u64::from(every) != 0 {
+                continue;
+            }
+            let from = self.npc_rooms[npc];
+            let exits = &self.world.room(from).exits;
+            if exits.is_empty() {
+                continue;
+            }
+            let (direction, to) = exits[self.rng.range(0, exits.len() as i32 - 1) as usize];
+            let name = self.world.npcs[npc].name.clone();
+            self.npc_rooms[npc] = to;
+            self.tell_room(from, None, format!("{} leaves {}.", name, direction.name()));
+            self.tell_room(to, None, format!("{} arrives.", name));
+            self.dirty = true;
+        }
+    }
+
+    /// Attack and defense including level and equipment.
+    pub fn player_stats(&self, id: PlayerId) -> Stats {
+        let player = self.player(id);
+        let mut stats = Stats {
+            attack: BASE_ATTACK + player.level,
+            defense: BASE_DEFENSE + player.level / 2,
+        };
+        for item in [player.weapon, player.armor].into_iter().flatten() {
+            stats.attack += self.world.item(item).attack;
+            stats.defense += self.world.item(item).defense;
+        }
+        stats
+    }
+
+    /// Living monsters in `room`.
+    fn monsters_in(&self, room: RoomId) -> Vec<MonsterId> {
+        (0..self.monsters.len())
+            .filter(|id| self.monsters[*id].alive() && self.world.monsters[*id].room == room)
+            .collect()
+    }
+
+    /// NPCs (indices in [`World::npcs`]) in `room`.
+    fn npcs_in(&self, room: RoomId) -> Vec<usize> {
+        (0..self.npc_rooms.len())
+            .filter(|npc| self.npc_rooms[*npc] == room)
+            .collect()
+    }
+
+    /// Players in the same room as `id`, not counting `id`.
+    fn others_here(&self, id: PlayerId) -> Vec<PlayerId> {
+        let room = self.player(id).room;
+        self.players()
+            .filter(|(other, player)| *other != id && player.room == room)
+            .map(|(other, _)| other)
+            .collect()
+    }
+
+    /// `You see: Bob, ghost, rat, torch.`, or `None` if the room is empty.
+    pub fn contents_line(&self, id: PlayerId) -> Option<String> {
+        let world = &self.world;
+        let room = self.player(id).room;
+        let names: Vec<&str> = self
+            .others_here(id)
+            .into_iter()
+            .map(|other| self.player(other).name.as_str())
+            .chain(
+                self.npcs_in(room)
+                    .into_iter()
+                    .map(|npc| world.npcs[npc].name.as_str()),
+            )
+            .chain(
+                self.monsters_in(room)
+                    .into_iter()
+                    .map(|monster| world.monsters[monster].name.as_str()),
+            )
+            .chain(
+                world
+                    .room(room)
+                    .items
+                    .iter()
+                    .map(|item| world.item(*item).name.as_str()),
+            )
+            .collect();
+        (!names.is_empty()).then(|| format!("You see: {}.", names.join(", ")))
+    }
+
+    /// Sends the player's room as output; the display shows it in the room panel.
+    pub fn look(&mut self, id: PlayerId) {
+        let room = self.world.room(self.player(id).room);
+        let mut lines = vec![format!("== {} ==", room.name), room.description.clone()];
+        lines.extend(self.contents_line(id));
+        lines.push(room.exits_line());
+        for line in lines {
+            self.echo(id, line);
+        }
+        self.dirty = true;
+    }
+
+    /// Shows player `id` the events whose trigger `matches`, except `once` events
+    /// they have seen. Returns whether any fired.
+    fn fire_events(&mut self, id: PlayerId, matches: impl Fn(Trigger) -> bool) -> bool {
+        let mut any = false;
+        for index in 0..self.world.events.len() {
+            let event = &self.world.events[index];
+            if matches(event.trigger) && !(event.once && self.player(id).fired[index]) {
+                let message = event.message.clone();
+                self.player_mut(id).fired[index] = true;
+                self.add_message(id, message);
+                any = true;
+            }
+        }
+        any
+    }
+
+    /// Moves the player to `room` and fires its enter events. Any conversation
+    /// is left behind.
+    fn enter_room(&mut self, id: PlayerId, room: RoomId) {
+        let player = self.player_mut(id);
+        player.room = room;
+        player.talking = None;
+        self.look(id);
+        self.fire_events(id, |trigger| trigger == Trigger::Enter(room));
+    }
+
+    fn go(&mut self, id: PlayerId, direction: Direction) {
+        let from = self.player(id).room;
+        match self.world.room(from).exit(direction) {
+            Some(room) => {
+                let name = self.player(id).name.clone();
+                let room_name = self.world.room(room).name.clone();
+                self.add_message(
+                    id,
+                    format!("You go {} to the {}.", direction.name(), room_name),
+                );
+                self.tell_room(
+                    from,
+                    Some(id),
+                    format!("{} leaves {}.", name, direction.name()),
+                );
+                self.tell_room(room, Some(id), format!("{} arrives.", name));
+                self.enter_room(id, room);
+            }
+            None => self.add_message(id, format!("You can't go {} from here.", direction.name())),
+        }
+    }
+
+    /// Describes an item in the room or inventory, or a monster, player or NPC
+    /// in the room.
+    fn examine(&mut self, id: PlayerId, name: &str) {
+        let world = &self.world;
+        let player = self.player(id);
+        let room = world.room(player.room);
+        let others = self.others_here(id);
+        let other_names: Vec<&str> = others
+            .iter()
+            .map(|other| self.player(*other).name.as_str())
+            .collect();
+        let description = if let Some(index) = world.find_item(&player.inventory, name) {
+            world.item(player.inventory[index]).description.clone()
+        } else if let Some(index) = world.find_item(&room.items, name) {
+            world.item(room.items[index]).description.clone()
+        } else if let Some(monster) = self.find_monster(id, name) {
+            let data = &world.monsters[monster];
+            if self.monsters[monster].health < data.health {
+                format!("{} It is wounded.", data.description)
+            } else {
+                data.description.clone()
+            }
+        } else if let Some(index) = find_name(&other_names, name) {
+            let other = self.player(others[index]);
+            format!("{} is a level {} adventurer.", other.name, other.level)
+        } else {
+            match self.find_npc(id, name) {
+                Some(npc) => world.npcs[npc].description.clone(),
+                None => format!("You see no {} here.", name),
+            }
+        };
+        self.add_message(id, description);
+    }
+
+    fn take_item(&mut self, id: PlayerId, name: &str) {
+        let room = self.player(id).room;
+        match self.world.find_item(&self.world.room(room).items, name) {
+            Some(index) => {
+                let item = self.world.room_mut(room).items.remove(index);
+                self.player_mut(id).inventory.push(item);
+                let item_name = self.world.item(item).name.clone();
+                let who = self.player(id).name.clone();
+                self.add_message(id, format!("You take the {}.", item_name));
+                self.tell_room(room, Some(id), format!("{} takes the {}.", who, item_name));
+            }
+            None => self.add_message(id, format!("There is no {} here.", name)),
+        }
+    }
+
+    fn drop_item(&mut self, id: PlayerId, name: &str) {
+        match self.world.find_item(&self.player(id).inventory, name) {
+            Some(index) => {
+                let player = self.player_mut(id);
+                let item = player.inventory.remove(index);
+                player.unequip(item);
+                let room = player.room;
+                self.world.room_mut(room).items.push(item);
+                let item_name = self.world.item(item).name.clone();
+                let who = self.player(id).name.clone();
+                self.add_message(id, format!("You drop the {}.", item_name));
+                self.tell_room(room, Some(id), format!("{} drops the {}.", who, item_name));
+            }
+            None => self.add_message(id, format!("You don't carry a {}.", name)),
+        }
+    }
+
+    fn show_inventory(&mut self, id: PlayerId) {
+        let player = self.player(id);
+        if player.inventory.is_empty() {
+            self.add_message(id, "You carry nothing.");
+            return;
+        }
+        let names: Vec<String> = player
+            .inventory
+            .iter()
+            .map(|item| {
+                let name = &self.world.item(*item).name;
+                if player.is_equipped(*item) {
+                    format!("{} (equipped)", name)
+                } else {
+                    name.clone()
+                }
+            })
+            .collect();
+        self.add_message(id, format!("You carry: {}.", names.join(", ")));
+    }
+
+    fn equip(&mut self, id: PlayerId, name: &str) {
+        let Some(index) = self.world.find_item(&self.player(id).inventory, name) else {
+            self.add_message(id, format!("You don't carry a {}.", name));
+            return;
+        };
+        let player = self.players.get_mut(&id).expect("unknown player");
+        let item_id = player.inventory[index];
+        let item = self.world.item(item_id);
+        let message = match item.slot {
+            Some(Slot::Weapon) => {
+                player.weapon = Some(item_id);
+                format!("You wield the {}.", item.name)
+            }
+            Some(Slot::Armor) => {
+                player.armor = Some(item_id);
+                format!("You put on the {}.", item.name)
+            }
+            None => format!("You can't equip the {}.", item.name),
+        };
+        self.add_message(id, message);
+    }
+
+    fn use_item(&mut self, id: PlayerId, name: &str) {
+        let Some(index) = self.world.find_item(&self.player(id).inventory, name) else {
+            self.add_message(id, format!("You don't carry a {}.", name));
+            return;
+        };
+        let player = self.players.get_mut(&id).expect("unknown player");
+        let (item_id, room) = (player.inventory[index], player.room);
+        let item = self.world.item(item_id).clone();
+        if item.heal > 0 {
+            player.inventory.remove(index);
+            player.unequip(item_id);
+            player.health = (player.health + item.heal).min(player.max_health());
+        }
+        if let Some(message) = &item.use_message {
+            self.add_message(id, message.clone());
+        }
+        let fired = self.fire_events(id, |trigger| match trigger {
+            Trigger::Use { item, room: at } => item == item_id && at.unwrap_or(room) == room,
+            _ => false,
+        });
+        if item.use_message.is_none() && !fired {
+            self.add_message(id, format!("Nothing happens with the {}.", item.name));
+        }
+    }
+
+    /// A living monster in the player's room called `name`, or the first one if
+    /// `name` is empty.
+    fn find_monster(&self, id: PlayerId, name: &str) -> Option<MonsterId> {
+        let here = self.monsters_in(self.player(id).room);
+        if name.is_empty() {
+            return here.first().copied();
+        }
+        let names: Vec<&str> = here
+            .iter()
+            .map(|monster| self.world.monsters[*monster].name.as_str())
+            .collect();
+        find_name(&names, name).map(|index| here[index])
+    }
+
+    /// An NPC in the player's room called `name`.
+    fn find_npc(&self, id: PlayerId, name: &str) -> Option<usize> {
+        let here = self.npcs_in(self.player(id).room);
+        let names: Vec<&str> = here
+            .iter()
+            .map(|npc| self.world.npcs[*npc].name.as_str())
+            .collect();
+        find_name(&names, name).map(|index| here[index])
+    }
+
+    /// One round of combat: the player strikes, and a surviving monster strikes back.
+    fn attack(&mut self, id: PlayerId, target: Option<&str>) {
+        let Some(monster_id) = self.find_monster(id, target.unwrap_or("")) else {
+            match target {
+                Some(target) => self.add_message(id, format!("There is no {} to attack.", target)),
+                None => self.add_message(id, "There is nothing here to attack."),
+            }
+            return;
+        };
+        let monster = self.world.monsters[monster_id].clone();
+        let monster_stats = Stats {
+            attack: monster.attack,
+            defense: monster.defense,
+        };
+        let player_stats = self.player_stats(id);
+
+        match combat::strike(&mut self.rng, player_stats, monster_stats) {
+            Blow::Miss => self.add_message(id, format!("You miss the {}.", monster.name)),
+            Blow::Hit { damage, critical } => {
+                let state = &mut self.monsters[monster_id];
+                state.health = (state.health - damage).max(0);
+                let critical = if critical { " Critical hit!" } else { "" };
+                self.add_message(
+                    id,
+                    format!("You hit the {} for {}.{}", monster.name, damage, critical),
+                );
+            }
+        }
+
+        if !self.monsters[monster_id].alive() {
+            self.monsters[monster_id].respawn_in = monster.respawn;
+            let who = self.player(id).name.clone();
+            self.add_message(
+                id,
+                format!("The {} dies. You gain {} XP.", monster.name, monster.xp),
+            );
+            self.tell_room(
+                monster.room,
+                Some(id),
+                format!("{} kills the {}.", who, monster.name),
+            );
+            self.gain_xp(id, monster.xp);
+            return;
+        }
+
+        match combat::strike(&mut self.rng, monster_stats, player_stats) {
+            Blow::Miss => self.add_message(id, format!("The {} misses you.", monster.name)),
+            Blow::Hit { damage, .. } => {
+                let player = self.player_mut(id);
+                player.health = (player.health - damage).max(0);
+                let dead = player.health == 0;
+                self.add_message(id, format!("The {} hits you for {}.", monster.name, damage));
+                if dead {
+                    self.die(id);
+                }
+            }
+        }
+    }
+
+    fn gain_xp(&mut self, id: PlayerId, xp: i32) {
+        self.player_mut(id).xp += xp;
+        loop {
+            let player = self.player_mut(id);
+            if player.xp < player.xp_needed() {
+                break;
+            }
+            player.xp -= player.xp_needed();
+            player.level += 1;
+            player.health = player.max_health();
+            let level = player.level;
+            self.add_message(id, format!("You reach level {}!", level));
+        }
+    }
+
+    /// The player drops everything where they fell, loses the experience towards
+    /// the next level and wakes up at the start; monsters there recover.
+    fn die(&mut self, id: PlayerId) {
+        self.add_message(id, "You die!");
+        let player = self.player_mut(id);
+        let room = player.room;
+        let who = player.name.clone();
+        let dropped = std::mem::take(&mut player.inventory);
+        player.weapon = None;
+        player.armor = None;
+        player.xp = 0;
+        player.health = player.max_health();
+        self.tell_room(room, Some(id), format!("{} dies!", who));
+        self.world.room_mut(room).items.extend(dropped);
+        for monster in self.monsters_in(room) {
+            self.monsters[monster].health = self.world.monsters[monster].health;
+        }
+
+        let start = self.world.start;
+        let name = self.world.room(start).name.clone();
+        self.add_message(id, format!("You wake up in the {}.", name));
+        self.tell_room(start, Some(id), format!("{} wakes up here.", who));
+        self.enter_room(id, start);
+    }
+
+    fn show_stats(&mut self, id: PlayerId) {
+        let stats = self.player_stats(id);
+        let player = self.player(id);
+        let message = format!(
+            "Level {}, XP {}/{}, health {}/{}, attack {}, defense {}.",
+            player.level,
+            player.xp,
+            player.xp_needed(),
+            player.health,
+            player.max_health(),
+            stats.attack,
+            stats.defense
+        );
+        self.add_message(id, message);
+    }
+
+    fn say(&mut self, id: PlayerId, text: &str) {
+        let player = self.player(id);
+        let (room, who) = (player.room, player.name.clone());
+        self.add_message(id, format!("You say: {}", text));
+        self.tell_room(room, Some(id), format!("{} says: {}", who, text));
+    }
+
+    fn who(&mut self, id: PlayerId) {
+        let names: Vec<String> = self
+            .players()
+            .map(|(_, player)| format!("{} ({})", player.name, self.world.room(player.room).name))
+            .collect();
+        self.add_message(id, format!("Playing: {}.", names.join(", ")));
+    }
+
+    fn talk(&mut self, id: PlayerId, name: &str) {
+        let Some(npc) = self.find_npc(id, name) else {
+            self.add_message(id, format!("There is no {} here.", name));
+            return;
+        };
+        let npc_name = self.world.npcs[npc].name.clone();
+        if self.world.npcs[npc].dialogue.is_empty() {
+            self.add_message(id, format!("{} has nothing to say.", npc_name));
+            return;
+        }
+        let player = self.player(id);
+        let (room, who) = (player.room, player.name.clone());
+        self.tell_room(room, Some(id), format!("{} talks to {}.", who, npc_name));
+        self.show_topic(id, npc, 0);
+    }
+
+    /// Shows what `npc` says about `topic` and the numbered replies to it. The
+    /// conversation ends at a topic without replies.
+    fn show_topic(&mut self, id: PlayerId, npc: usize, topic: usize) {
+        let topic_data = &self.world.npcs[npc].dialogue[topic];
+        let text = topic_data.text.clone();
+        let answers: Vec<String> = topic_data
+            .answers
+            .iter()
+            .enumerate()
+            .map(|(index, (answer, _))| format!("{}) {}", index + 1, answer))
+            .collect();
+        self.add_message(id, text);
+        if answers.is_empty() {
+            self.player_mut(id).talking = None;
+        } else {
+            self.player_mut(id).talking = Some((npc, topic));
+            self.add_message(id, answers.join("  "));
+        }
+    }
+
+    fn answer(&mut self, id: PlayerId, number: usize) {
+        let Some((npc, topic)) = self.player(id).talking else {
+            self.add_message(id, "You aren't talking to anyone.");
+            return;
+        };
+        let data = &self.world.npcs[npc];
+        if self.npc_rooms[npc] != self.player(id).room {
+            let message = format!("{} is no longer here.", data.name);
+            self.player_mut(id).talking = None;
+            self.add_message(id, message);
+            return;
+        }
+        let answers = &data.dialogue[topic].answers;
+        match number.checked_sub(1).and_then(|index| answers.get(index)) {
+            Some((answer, next)) => {
+                let (line, next) = (format!("You: {}", answer), *next);
+                self.echo(id, line);
+                self.show_topic(id, npc, next);
+            }
+            None => {
+                let message = format!("Answer 1 to {}.", answers.len());
+                self.add_message(id, message);
+            }
+        }
+    }
+
+    fn help(&mut self, id: PlayerId) {
+        for line in parser::HELP {
+            self.echo(id, format!("  {}", line));
+        }
+        self.add_message(
+            id,
+            "Commands: look, go, take, drop, inventory, equip, attack, use, stats, say, who, talk, save, load, help.",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD: &str = "
+start hall
+
+[room hall]
+name Hall
+desc A bare hall.
+exit north cellar
+item sword
+item mail
+item amulet
+
+[room cellar]
+name Cellar
+desc Damp and dark.
+exit south hall
+
+[item sword]
+name sword
+slot weapon
+attack 3
+
+[item mail]
+name mail
+slot armor
+defense 2
+
+[item amulet]
+name amulet
+slot armor
+defense 1
+heal 4
+use The amulet crumbles to dust.
+
+[monster rat]
+name rat
+room cellar
+health 5
+attack 1
+xp 10
+respawn 2
+";
+
+    fn game() -> (GameState, PlayerId) {
+        let mut state = GameState::new(World::parse(WORLD).unwrap(), 1);
+        let id = state.join("Ann");
+        state.take_output(id);
+        (state, id)
+    }
+
+    fn run(state: &mut GameState, id: PlayerId, commands: &[&str]) -> Vec<String> {
+        for command in commands {
+            assert_eq!(state.handle_command(id, command), None);
+        }
+        state.take_output(id)
+    }
+
+    /// Attacks the rat until it dies (it can't do enough damage to win) and
+    /// returns the output of the fight.
+    fn kill_rat(state: &mut GameState, id: PlayerId) -> Vec<String> {
+        let mut output = Vec::new();
+        for _ in 0..100 {
+            if !state.monsters[0].alive() {
+                return output;
+            }
+            output.extend(run(state, id, &["attack rat"]));
+        }
+        panic!("the rat survived");
+    }
+
+    #[test]
+    fn equipment_adds_to_stats() {
+        let (mut state, id) = game();
+        let base = state.player_stats(id);
+        assert_eq!(
+            base,
+            Stats {
+                attack: BASE_ATTACK + 1,
+                defense: BASE_DEFENSE,
+            }
+        );
+
+        run(
+            &mut state,
+            id,
+            &["take sword", "take mail", "wield sword", "wear mail"],
+        );
+        assert_eq!(
+            state.player_stats(id),
+            Stats {
+                attack: base.attack + 3,
+                defense: base.defense + 2,
+            }
+        );
+
+        run(&mut state, id, &["drop sword"]);
+        assert_eq!(state.player_stats(id).attack, base.attack);
+        assert_eq!(state.player(id).weapon, None);
+    }
+
+    #[test]
+    fn using_up_an_equipped_item_unequips_it() {
+        let (mut state, id) = game();
+        run(&mut state, id, &["take amulet", "wear amulet"]);
+        state.player_mut(id).health = 1;
+
+        assert_eq!(
+            run(&mut state, id, &["use amulet"]),
+            ["The amulet crumbles to dust."]
+        );
+        let player = state.player(id);
+        assert!(player.inventory.is_empty());
+        assert_eq!(player.armor, None);
+        assert_eq!(player.health, 5);
+        assert_eq!(state.player_stats(id).defense, BASE_DEFENSE);
+    }
+
+    #[test]
+    fn level_stats_grow() {
+        let (mut state, id) = game();
+        state.player_mut(id).level = 4;
+        assert_eq!(
+            state.player_stats(id),
+            Stats {
+                attack: BASE_ATTACK + 4,
+                defense: BASE_DEFENSE + 2,
+            }
+        );
+        assert_eq!(
+            state.player(id).max_health(),
+            BASE_HEALTH + 4 * HEALTH_PER_LEVEL
+        );
+    }
+
+    #[test]
+    fn xp_can_gain_several_levels() {
+        let (mut state, id) = game();
+        state.player_mut(id).health = 1;
+        // 50 for level 2, 100 for level 3, 20 left over
+        state.gain_xp(id, 170);
+        let player = state.player(id);
+        assert_eq!(player.level, 3);
+        assert_eq!(player.xp, 20);
+        assert_eq!(player.health, player.max_health());
+        let output = state.take_output(id);
+        assert_eq!(output, ["You reach level 2!", "You reach level 3!"]);
+    }
+
+    #[test]
+    fn xp_below_next_level_just_adds_up() {
+        let (mut state, id) = game();
+        state.gain_xp(id, 30);
+        state.gain_xp(id, 19);
+        assert_eq!(state.player(id).level, 1);
+        assert_eq!(state.player(id).xp, 49);
+        assert!(state.take_output(id).is_empty());
+    }
+
+    #[test]
+    fn dying_drops_everything_and_returns_to_start() {
+        let (mut state, id) = game();
+        run(&mut state, id, &["take sword", "wield sword", "north"]);
+        state.monsters[0].health = 2;
+        state.player_mut(id).xp = 30;
+        state.take_output(id);
+
+        state.die(id);
+
+        let player = state.player(id);
+        assert_eq!(player.room, state.world.start);
+        assert!(player.inventory.is_empty());
+        assert_eq!(player.weapon, None);
+        assert_eq!(player.xp, 0);
+        assert_eq!(player.health, player.max_health());
+        let cellar = state.world.room_by_id("cellar").unwrap();
+        let sword = state.world.item_by_id("sword").unwrap();
+        assert_eq!(state.world.room(cellar).items, [sword]);
+        assert_eq!(state.monsters[0].health, 5, "the rat recovers");
+        let output = state.take_output(id);
+        assert_eq!(output[0], "You die!");
+        assert!(output.contains(&"You wake up in the Hall.".to_string()));
+    }
+
+    #[test]
+    fn killing_a_monster_gives_xp_and_starts_its_respawn() {
+        let (mut state, id) = game();
+        run(&mut state, id, &["north"]);
+        let output = kill_rat(&mut state, id);
+        assert_eq!(state.monsters[0].respawn_in, 2);
+        assert_eq!(state.player(id).xp, 10);
+        assert_eq!(output.last().unwrap(), "The rat dies. You gain 10 XP.");
+    }
+
+    #[test]
+    fn dead_monster_counts_down_then_respawns() {
+        let (mut state, id) = game();
+        run(&mut state, id, &["north"]);
+        kill_rat(&mut state, id);
+
+        state.simulate_turn();
+        assert!(!state.monsters[0].alive());
+        assert_eq!(state.monsters[0].respawn_in, 1);
+        assert!(state.take_output(id).is_empty());
+
+        state.simulate_turn();
+        assert_eq!(state.monsters[0].health, 5);
+        assert_eq!(state.monsters[0].respawn_in, 0);
+        assert_eq!(state.take_output(id), ["A rat appears!"]);
+    }
+
+    #[test]
+    fn monster_without_respawn_stays_dead() {
+        let (mut state, id) = game();
+        state.monsters[0] = MonsterState {
+            health: 0,
+            respawn_in: 0,
+        };
+        for _ in 0..10 {
+            state.simulate_turn();
+        }
+        assert!(!state.monsters[0].alive());
+        assert!(state.take_output(id).is_empty());
+    }
+
+    #[test]
+    fn same_seed_same_fight() {
+        let play = || {
+            let (mut state, id) = game();
+            run(&mut state, id, &["north"]);
+            run(&mut state, id, &["attack"; 6])
+        };
+        assert_eq!(play(), play());
+    }
+
+    /// A world with NPCs and events, kept apart from [`WORLD`] so its messages
+    /// don't show up in the other tests.
+    const STORY: &str = "
+start hall
+
+[room hall]
+name Hall
+exit north cellar
+exit east yard
+item lamp
+
+[room cellar]
+name Cellar
+exit south hall
+
+[room yard]
+name Yard
+exit west hall
+
+[item lamp]
+name lamp
+
+[npc cat]
+name cat
+room hall
+wander 2
+
+[npc ghost]
+name ghost
+room cellar
+talk start Who goes there?
+answer start name A friend.
+answer start bye Nobody.
+talk name Then sit with me a while.
+answer name bye Goodbye.
+talk bye Begone.
+
+[event draught]
+on enter cellar
+message A cold draught.
+
+[event first-visit]
+on enter cellar
+once
+message You have never been here before.
+
+[event bell]
+on timer 3
+message A bell rings.
+
+[event drip]
+on timer 2 cellar
+message Water drips.
+
+[event lamp-ghost]
+on use lamp cellar
+message The ghost flinches from the light.
+";
+
+    fn story(seed: u64) -> (GameState, PlayerId) {
+        let mut state = GameState::new(World::parse(STORY).unwrap(), seed);
+        let id = state.join("Ann");
+        state.take_output(id);
+        (state, id)
+    }
+
+    fn said(output: &[String], line: &str) -> bool {
+        output.iter().any(|said| said == line)
+    }
+
+    #[test]
+    fn talk_and_answers_walk_the_dialogue() {
+        let (mut state, id) = story(1);
+        run(&mut state, id, &["north"]);
+        assert_eq!(
+            run(&mut state, id, &["talk to ghost"]),
+            ["Who goes there?", "1) A friend.  2) Nobody."]
+        );
+        assert_eq!(run(&mut state, id, &["3"]), ["Answer 1 to 2."]);
+        assert_eq!(run(&mut state, id, &["answer 0"]), ["Answer 1 to 2."]);
+        assert_eq!(
+            run(&mut state, id, &["1"]),
+            ["You: A friend.", "Then sit with me a while.", "1) Goodbye."]
+        );
+        assert_eq!(run(&mut state, id, &["1"]), ["You: Goodbye.", "Begone."]);
+        assert_eq!(
+            run(&mut state, id, &["1"]),
+            ["You aren't talking to anyone."]
+        );
+    }
+
+    #[test]
+    fn leaving_the_room_ends_the_conversation() {
+        let (mut state, id) = story(1);
+        run(&mut state, id, &["north", "talk ghost", "south"]);
+        assert_eq!(
+            run(&mut state, id, &["2"]),
+            ["You aren't talking to anyone."]
+        );
+    }
+
+    #[test]
+    fn once_events_fire_once_per_player() {
+        let (mut state, ann) = story(1);
+        let first = run(&mut state, ann, &["north"]);
+        assert!(said(&first, "A cold draught."));
+        assert!(said(&first, "You have never been here before."));
+
+        let again = run(&mut state, ann, &["south", "north"]);
+        assert!(said(&again, "A cold draught."));
+        assert!(!said(&again, "You have never been here before."));
+
+        let bob = state.join("Bob");
+        state.take_output(bob);
+        let output = run(&mut state, bob, &["north"]);
+        assert!(said(&output, "You have never been here before."));
+    }
+
+    #[test]
+    fn timer_events_fire_on_their_turn_and_in_their_room() {
+        let (mut state, id) = story(1);
+        let mut heard = Vec::new();
+        for turn in 1..=6 {
+            state.simulate_turn();
+            let output = state.take_output(id);
+            assert!(!said(&output, "Water drips."), "not in the cellar");
+            if said(&output, "A bell rings.") {
+                heard.push(turn);
+            }
+        }
+        assert_eq!(heard, [3, 6]);
+
+        run(&mut state, id, &["north"]);
+        state.simulate_turn();
+        assert!(!said(&state.take_output(id), "Water drips."));
+        state.simulate_turn();
+        assert!(said(&state.take_output(id), "Water drips."));
+    }
+
+    #[test]
+    fn use_events_fire_where_the_item_is_used() {
+        let (mut state, id) = story(1);
+        assert_eq!(
+            run(&mut state, id, &["take lamp", "use lamp"]),
+            ["You take the lamp.", "Nothing happens with the lamp."]
+        );
+        run(&mut state, id, &["north"]);
+        assert_eq!(
+            run(&mut state, id, &["use lamp"]),
+            ["The ghost flinches from the light."]
+        );
+    }
+
+    #[test]
+    fn npcs_wander_through_exits_the_same_way_for_a_seed() {
+        let wander = |seed| {
+            let (mut state, _) = story(seed);
+            let cat = state.world.npc_by_id("cat").unwrap();
+            let mut rooms = vec![state.npc_rooms[cat]];
+            for turn in 1..=20 {
+                state.simulate_turn();
+                let (from, to) = (*rooms.last().unwrap(), state.npc_rooms[cat]);
+                if turn % 2 == 0 {
+                    let exits = &state.world.room(from).exits;
+                    assert!(exits.iter().any(|(_, room)| *room == to), "turn {}", turn);
+                } else {
+                    assert_eq!(from, to, "turn {}", turn);
+                }
+                rooms.push(to);
+            }
+            rooms
+        };
+        assert_eq!(wander(7), wander(7));
+    }
+}
@@ -0,0 +1,463 @@
+#![cfg(feature = "graphics-support")]
+
+//! # mud_game_app
+//! A simple text-based MUD game running on ESP32 via serial, or as a multiplayer
+//! server over Wi-Fi with the display as its status dashboard. Both also run on
+//! boards without a display.
+//!  
+
+use anyhow::{anyhow, Result};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::Text,
+};
+use esp_idf_hal::{delay::FreeRtos, peripherals::Peripherals};
+use esp_idf_svc::{eventloop::EspSystemEventLoop, nvs::EspDefaultNvsPartition};
+use esp_idf_sys as _;
+use log::{error, info, warn};
+use std::fs;
+use std::io::{stdin, stdout, ErrorKind, Read, Stdin, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::thread;
+
+use super::App;
+use crate::board::{self, map_st7789_error};
+use crate::storage;
+use crate::wifi;
+
+mod combat;
+mod engine;
+mod game;
+mod parser;
+mod render;
+mod save;
+mod server;
+mod world;
+
+use engine::{GameEngine, TICKS_PER_TURN};
+use game::{GameState, Request};
+use render::{Renderer, St7789Renderer, TextRenderer, LINE_HEIGHT};
+use server::Server;
+use world::World;
+
+/// World file on the flash filesystem that replaces the built-in world.
+const WORLD_FILE: &str = "/spiffs/world.txt";
+/// Saved game, restored at boot. Written to [`SAVE_FILE_TMP`] first, since SPIFFS
+/// can't rename over an existing file.
+const SAVE_FILE: &str = "/spiffs/mud.sav";
+const SAVE_FILE_TMP: &str = "/spiffs/mud.sav.tmp";
+/// Game turns between autosaves.
+const AUTOSAVE_TURNS: u64 = 20;
+/// Name of the single player at the console.
+const PLAYER_NAME: &str = "Adventurer";
+/// Length of an engine tick.
+const TICK_MS: u32 = 100;
+
+/// Collects a line typed on the serial console, echoing it as it arrives.
+struct ConsoleInput {
+    stdin: Stdin,
+    line: String,
+}
+
+impl ConsoleInput {
+    fn new() -> Self {
+        Self {
+            stdin: stdin(),
+            line: String::new(),
+        }
+    }
+
+    /// Reads whatever is available without blocking; returns a line once Enter is pressed.
+    fn poll(&mut self) -> Option<String> {
+        let mut byte = [0u8; 1];
+        // The console may be non-blocking; stop when no byte is waiting.
+        while let Ok(1) = self.stdin.read(&mut byte) {
+            match byte[0] {
+                b'\r' | b'\n' => {
+                    if self.line.is_empty() {
+                        continue;
+                    }
+                    println!();
+                    return Some(std::mem::take(&mut self.line));
+                }
+                0x08 | 0x7f => {
+                    if self.line.pop().is_some() {
+                        print!("\x08 \x08");
+                    }
+                }
+                byte if byte.is_ascii_graphic() || byte == b' ' => {
+                    self.line.push(byte as char);
+                    print!("{}", byte as char);
+                }
+                _ => {}
+            }
+            let _ = stdout().flush();
+        }
+        None
+    }
+}
+
+/// Registry entry for [`run`].
+pub struct MudGameApp;
+
+impl App for MudGameApp {
+    fn name(&self) -> &'static str {
+        "mud_game_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Text MUD game on the display or console"
+    }
+
+    fn run(&self) -> Result<()> {
+        run()
+    }
+}
+
+/// Registry entry for [`run_server`].
+pub struct MudServerApp;
+
+impl App for MudServerApp {
+    fn name(&self) -> &'static str {
+        "mud_server_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Multiplayer MUD over telnet"
+    }
+
+    fn run(&self) -> Result<()> {
+        run_server()
+    }
+}
+
+/// Mounts the flash filesystem; without it the game runs on the built-in world.
+fn mount_storage() -> bool {
+    match storage::mount() {
+        Ok(_) => true,
+        Err(e) => {
+            warn!(
+                "SPIFFS not mounted, using the built-in world without saves: {}",
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Seed from the hardware RNG, so every boot plays out differently.
+fn random_seed() -> u64 {
+    (u64::from(unsafe { esp_idf_sys::esp_random() }) << 32)
+        | u64::from(unsafe { esp_idf_sys::esp_random() })
+}
+
+/// [`WORLD_FILE`] if the filesystem has one that parses, else the built-in world.
+fn load_world(fs_mounted: bool) -> World {
+    if !fs_mounted {
+        return World::builtin();
+    }
+    let text = match fs::read_to_string(WORLD_FILE) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return World::builtin(),
+        Err(e) => {
+            warn!(
+                "Could not read {}, using the built-in world: {}",
+                WORLD_FILE, e
+            );
+            return World::builtin();
+        }
+    };
+    match World::parse(&text) {
+        Ok(world) => {
+            info!("Loaded world from {}", WORLD_FILE);
+            for warning in world.warnings() {
+                warn!("{}: {}", WORLD_FILE, warning);
+            }
+            world
+        }
+        Err(e) => {
+            error!("{}: {}; using the built-in world", WORLD_FILE, e);
+            World::builtin()
+        }
+    }
+}
+
+/// Writes `text` to [`SAVE_FILE`], keeping the old save until the new one is complete.
+fn write_save(text: &str) -> std::io::Result<()> {
+    fs::write(SAVE_FILE_TMP, text)?;
+    match fs::remove_file(SAVE_FILE) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    fs::rename(SAVE_FILE_TMP, SAVE_FILE)
+}
+
+/// The saved game, or `None` if there is none. Falls back to [`SAVE_FILE_TMP`] in
+/// case the board reset between removing the old save and renaming the new one.
+fn read_save() -> std::io::Result<Option<String>> {
+    for path in [SAVE_FILE, SAVE_FILE_TMP] {
+        match fs::read_to_string(path) {
+            Ok(text) => return Ok(Some(text)),
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Saves the game unless it is unchanged since `last_saved`. Returns whether it
+/// is now on flash.
+fn autosave(engine: &GameEngine, last_saved: &mut String) -> bool {
+    let text = save::to_text(engine.state(), engine.player());
+    if text == *last_saved {
+        return true;
+    }
+    match write_save(&text) {
+        Ok(()) => {
+            *last_saved = text;
+            true
+        }
+        Err(e) => {
+            warn!("Could not write {}: {}", SAVE_FILE, e);
+            false
+        }
+    }
+}
+
+/// Carries out a `save` or `load` typed by the player.
+fn handle_request(
+    engine: &mut GameEngine,
+    request: Request,
+    fs_mounted: bool,
+    last_saved: &mut String,
+) {
+    let player = engine.player();
+    let reply = if !fs_mounted {
+        "Saving needs the flash filesystem."
+    } else {
+        match request {
+            Request::Save => {
+                if autosave(engine, last_saved) {
+                    "Game saved."
+                } else {
+                    "Could not save the game."
+                }
+            }
+            Request::Load => match read_save() {
+                Ok(Some(text)) => match save::restore(engine.state_mut(), player, &text) {
+                    Ok(()) => {
+                        *last_saved = text;
+                        "Game loaded."
+                    }
+                    Err(e) => {
+                        warn!("{}: {}", SAVE_FILE, e);
+                        "The saved game is damaged."
+                    }
+                },
+                Ok(None) => "There is no saved game.",
+                Err(e) => {
+                    warn!("Could not read {}: {}", SAVE_FILE, e);
+                    "Could not load the game."
+                }
+            },
+        }
+    };
+    engine.state_mut().add_message(player, reply);
+}
+
+/// Runs the MUD game shell:  
+/// listens on UART, processes commands, and responds.
+///
+/// Type `help` on the serial console for the commands.
+pub fn run() -> Result<()> {
+    let peripherals = Peripherals::take()?;
+
+    // The display if the board has one (pins and SPI setup come from the board
+    // profile), else a status line on the console
+    let (mut renderer, _backlight): (Box<dyn Renderer>, _) = match board::BOARD.display {
+        Some(_) => {
+            let (display, backlight) = board::init_display(peripherals.spi2)?;
+            (Box::new(St7789Renderer::new(display)), Some(backlight))
+        }
+        None => (Box::new(TextRenderer::new(stdout())), None),
+    };
+
+    let fs_mounted = mount_storage();
+    let mut engine = GameEngine::new(load_world(fs_mounted), random_seed(), PLAYER_NAME);
+
+    // Pick up where the last session left off
+    let mut last_saved = String::new();
+    if fs_mounted {
+        let me = engine.player();
+        match read_save() {
+            Ok(Some(text)) => match save::restore(engine.state_mut(), me, &text) {
+                Ok(()) => {
+                    info!("Restored game from {}", SAVE_FILE);
+                    last_saved = text;
+                    engine.state_mut().add_message(me, "Welcome back.");
+                }
+                Err(e) => warn!("{}: {}; starting a new game", SAVE_FILE, e),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("Could not read {}: {}", SAVE_FILE, e),
+        }
+    }
+
+    let mut input = ConsoleInput::new();
+
+    // Main game loop
+    loop {
+        if let Some(line) = input.poll() {
+            if let Some(request) = engine.handle_command(&line) {
+                handle_request(&mut engine, request, fs_mounted, &mut last_saved);
+            }
+        }
+
+        if engine.tick() && fs_mounted && engine.turns() % AUTOSAVE_TURNS == 0 {
+            autosave(&engine, &mut last_saved);
+        }
+        for line in engine.take_output() {
+            println!("{}", line);
+        }
+
+        // Only redraw when there are changes
+        if let Some(view) = engine.take_view() {
+            renderer.render(&view)?;
+        }
+
+        FreeRtos::delay_ms(TICK_MS);
+    }
+}
+
+/// One line per player on the server dashboard: name and stats, then their room.
+type PlayerStatus = (String, String);
+
+/// Server address, player count and who is where.
+fn draw_dashboard(
+    display: &mut board::Display,
+    ip: Ipv4Addr,
+    players: &[PlayerStatus],
+) -> Result<()> {
+    let header_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_RED);
+    let text_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_WHITE);
+
+    display.clear(Rgb565::BLACK).map_err(map_st7789_error)?;
+    Text::new("MUD server", Point::new(10, 10), header_style)
+        .draw(display)
+        .map_err(map_st7789_error)?;
+    Text::new(
+        &format!("telnet {} {}", ip, server::DEFAULT_PORT),
+        Point::new(10, 25),
+        text_style,
+    )
+    .draw(display)
+    .map_err(map_st7789_error)?;
+    Text::new(
+        &format!("Players: {}/{}", players.len(), server::MAX_PLAYERS),
+        Point::new(10, 50),
+        header_style,
+    )
+    .draw(display)
+    .map_err(map_st7789_error)?;
+
+    let mut y = 50 + LINE_HEIGHT;
+    for (player, room) in players {
+        Text::new(player, Point::new(10, y), text_style)
+            .draw(display)
+            .map_err(map_st7789_error)?;
+        Text::new(room, Point::new(20, y + LINE_HEIGHT), text_style)
+            .draw(display)
+            .map_err(map_st7789_error)?;
+        y += 2 * LINE_HEIGHT;
+    }
+    Ok(())
+}
+
+/// Runs the multiplayer server:
+/// joins Wi-Fi, accepts telnet players on [`server::DEFAULT_PORT`] and shows
+/// who is playing where on the display, if the board has one.
+///
+/// Wi-Fi credentials come from NVS, see [`crate::wifi`].
+pub fn run_server() -> Result<()> {
+    let sysloop = EspSystemEventLoop::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
+    let peripherals = Peripherals::take()?;
+
+    // Display initialization (pins and SPI setup come from the board profile)
+    let mut display = match board::BOARD.display {
+        Some(_) => Some(board::init_display(peripherals.spi2)?),
+        None => None,
+    };
+    if let Some((display, _backlight)) = &mut display {
+        let text_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_WHITE);
+        Text::new("Connecting to Wi-Fi...", Point::new(10, 10), text_style)
+            .draw(display)
+            .map_err(map_st7789_error)?;
+    }
+
+    let credentials = wifi::load_credentials(&nvs)?.ok_or_else(|| {
+        anyhow!(
+            "no Wi-Fi credentials in NVS (namespace '{}')",
+            wifi::NVS_NAMESPACE
+        )
+    })?;
+    // Keeps the connection up while the server runs
+    let wifi = wifi::connect(peripherals.modem, sysloop, nvs, &credentials)?;
+    let ip = wifi.wifi().sta_netif().get_ip_info()?.ip;
+
+    let fs_mounted = mount_storage();
+    let server = Server::new(GameState::new(load_world(fs_mounted), random_seed()));
+    let listener = TcpListener::bind(("0.0.0.0", server::DEFAULT_PORT))?;
+    info!("MUD server listening on {}:{}", ip, server::DEFAULT_PORT);
+    {
+        let server = server.clone();
+        thread::Builder::new().stack_size(8192).spawn(move || {
+            if let Err(e) = server.serve(listener) {
+                error!("MUD server failed: {:?}", e);
+            }
+        })?;
+    }
+
+    // Game turns and the status dashboard
+    let mut ticks: u32 = 0;
+    loop {
+        if ticks % TICKS_PER_TURN == 0 {
+            server.tick();
+        }
+        ticks = ticks.wrapping_add(1);
+
+        // Copy what to show, so sessions aren't kept waiting on the display
+        let status = {
+            let mut game = server.game();
+            if game.dirty {
+                game.dirty = false;
+                let players: Vec<PlayerStatus> = game
+                    .players()
+                    .map(|(_, player)| {
+                        (
+                            format!(
+                                "{}  {}/{}  L{}",
+                                player.name,
+                                player.health,
+                                player.max_health(),
+                                player.level
+                            ),
+                            game.world.room(player.room).name.clone(),
+                        )
+                    })
+                    .collect();
+                Some(players)
+            } else {
+                None
+            }
+        };
+
+        if let (Some((display, _backlight)), Some(players)) = (&mut display, status) {
+            draw_dashboard(display, ip, &players)?;
+        }
+
+        FreeRtos::delay_ms(TICK_MS);
+    }
+}
@@ -0,0 +1,285 @@
+//! # mud_game_app::parser
+//! Turns a line typed by the player into a [`Command`].
+//!
+//! Verbs are case-insensitive and have short forms (`l`, `x`, `n`, `get`, `i`, `kill`, `'`, `?`).
+//! Item and target names may span several words (`take rusty sword`). A bare number
+//! answers the NPC you are talking to.
+//!
+
+use std::fmt;
+
+use super::world::Direction;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Look,
+    /// `look <thing>`: describe an item or NPC.
+    Examine(String),
+    Go(Direction),
+    Take(String),
+    Drop(String),
+    Inventory,
+    /// Wield a weapon or put on armor.
+    Equip(String),
+    /// Attack the named target, or whatever is there.
+    Attack(Option<String>),
+    Use(String),
+    Stats,
+    /// Talk to everyone in the room.
+    Say(String),
+    /// List the players in the game.
+    Who,
+    /// Start a conversation with an NPC.
+    Talk(String),
+    /// Pick a reply, numbered from 1, in the current conversation.
+    Answer(usize),
+    /// Write the game to flash.
+    Save,
+    /// Go back to the last saved game.
+    Load,
+    Help,
+}
+
+/// Verbs with their arguments, as listed by `help`.
+pub const HELP: &[&str] = &[
+    "look (l), look <thing> (x)",
+    "go <direction> (or n, e, s, w, u, d)",
+    "take <item>, drop <item>",
+    "inventory (i)",
+    "equip <item> (wield, wear)",
+    "attack [target] (kill, a)",
+    "use <item>",
+    "stats (score)",
+    "say <text> (')",
+    "who",
+    "talk <npc>, then 1, 2, ... to answer",
+    "save, load",
+    "help (?)",
+];
+
+/// Why a line is not a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    UnknownVerb(String),
+    UnknownDirection(String),
+    /// The verb needs an argument, e.g. `take` without an item. Holds the question to ask.
+    MissingArgument(&'static str),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Say something."),
+            ParseError::UnknownVerb(verb) => {
+                write!(f, "I don't know how to '{}'. Type 'help'.", verb)
+            }
+            ParseError::UnknownDirection(word) => write!(f, "'{}' is not a direction.", word),
+            ParseError::MissingArgument(question) => write!(f, "{}", question),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses one line of player input.
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    // `'hello` is short for `say hello`
+    if let Some(text) = line.trim_start().strip_prefix('\'') {
+        let text = text.trim();
+        return if text.is_empty() {
+            Err(ParseError::MissingArgument("Say what?"))
+        } else {
+            Ok(Command::Say(text.to_string()))
+        };
+    }
+
+    let mut words = line.split_whitespace();
+    let verb = words.next().ok_or(ParseError::Empty)?.to_ascii_lowercase();
+    let rest = words.collect::<Vec<_>>().join(" ");
+    let argument = |question| {
+        if rest.is_empty() {
+            Err(ParseError::MissingArgument(question))
+        } else {
+            Ok(rest.clone())
+        }
+    };
+
+    match verb.as_str() {
+        "look" | "l" if rest.is_empty() => Ok(Command::Look),
+        "look" | "l" | "examine" | "x" => {
+            let thing = argument("Look at what?")?;
+            let thing = thing.strip_prefix("at ").unwrap_or(&thing);
+            Ok(Command::Examine(thing.to_string()))
+        }
+        "go" | "walk" => {
+            let word = argument("Go where?")?;
+            Direction::parse(&word)
+                .map(Command::Go)
+                .ok_or(ParseError::UnknownDirection(word))
+        }
+        "take" | "get" => argument("Take what?").map(Command::Take),
+        "drop" => argument("Drop what?").map(Command::Drop),
+        "inventory" | "inv" | "i" => Ok(Command::Inventory),
+        "attack" | "kill" | "a" => Ok(Command::Attack((!rest.is_empty()).then(|| rest.clone()))),
+        "equip" | "wield" | "wear" => argument("Equip what?").map(Command::Equip),
+        "use" => argument("Use what?").map(Command::Use),
+        "stats" | "score" => Ok(Command::Stats),
+        "say" => argument("Say what?").map(Command::Say),
+        "who" => Ok(Command::Who),
+        "talk" | "ask" => {
+            let npc = argument("Talk to whom?")?;
+            let npc = npc.strip_prefix("to ").unwrap_or(&npc);
+            Ok(Command::Talk(npc.to_string()))
+        }
+        "answer" | "reply" => argument("Answer what?")?
+            .parse()
+            .map(Command::Answer)
+            .map_err(|_| ParseError::MissingArgument("Answer with the number of a reply.")),
+        "save" => Ok(Command::Save),
+        "load" | "restore" => Ok(Command::Load),
+        "help" | "?" => Ok(Command::Help),
+        _ if rest.is_empty() => match (Direction::parse(&verb), verb.parse()) {
+            (Some(direction), _) => Ok(Command::Go(direction)),
+            (None, Ok(number)) => Ok(Command::Answer(number)),
+            (None, Err(_)) => Err(ParseError::UnknownVerb(verb)),
+        },
+        _ => Err(ParseError::UnknownVerb(verb)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(line: &str) -> Command {
+        parse(line).unwrap()
+    }
+
+    #[test]
+    fn every_alias() {
+        let thing = || "rusty sword".to_string();
+        let cases: &[(&[&str], Command)] = &[
+            (&["look", "l", "LOOK"], Command::Look),
+            (&["examine", "x", "look", "l"], Command::Examine(thing())),
+            (&["go", "walk"], Command::Go(Direction::North)),
+            (&["take", "get"], Command::Take(thing())),
+            (&["drop"], Command::Drop(thing())),
+            (&["equip", "wield", "wear"], Command::Equip(thing())),
+            (&["attack", "kill", "a"], Command::Attack(Some(thing()))),
+            (&["use"], Command::Use(thing())),
+            (&["say"], Command::Say(thing())),
+            (&["talk", "ask"], Command::Talk(thing())),
+        ];
+        for (verbs, command) in cases {
+            for verb in *verbs {
+                let argument = match command {
+                    Command::Look => "",
+                    Command::Go(_) => " north",
+                    _ => " rusty sword",
+                };
+                assert_eq!(ok(&format!("{}{}", verb, argument)), *command, "{}", verb);
+            }
+        }
+
+        let bare: &[(&[&str], Command)] = &[
+            (&["inventory", "inv", "i"], Command::Inventory),
+            (&["attack", "kill", "a"], Command::Attack(None)),
+            (&["stats", "score"], Command::Stats),
+            (&["who"], Command::Who),
+            (&["save"], Command::Save),
+            (&["load", "restore"], Command::Load),
+            (&["help", "?"], Command::Help),
+        ];
+        for (verbs, command) in bare {
+            for verb in *verbs {
+                assert_eq!(ok(verb), *command, "{}", verb);
+            }
+        }
+        assert_eq!(ok("answer 2"), Command::Answer(2));
+        assert_eq!(ok("reply 2"), Command::Answer(2));
+    }
+
+    #[test]
+    fn quote_is_say() {
+        assert_eq!(ok("'hello there "), Command::Say("hello there".to_string()));
+        assert_eq!(ok("  ' hi"), Command::Say("hi".to_string()));
+        assert_eq!(parse("'  "), Err(ParseError::MissingArgument("Say what?")));
+    }
+
+    #[test]
+    fn bare_number_answers() {
+        assert_eq!(ok("1"), Command::Answer(1));
+        assert_eq!(ok(" 12 "), Command::Answer(12));
+        assert_eq!(
+            parse("answer two"),
+            Err(ParseError::MissingArgument(
+                "Answer with the number of a reply."
+            ))
+        );
+    }
+
+    #[test]
+    fn bare_directions() {
+        for (words, direction) in [
+            (["north", "n"], Direction::North),
+            (["east", "e"], Direction::East),
+            (["south", "s"], Direction::South),
+            (["west", "w"], Direction::West),
+            (["up", "u"], Direction::Up),
+            (["down", "d"], Direction::Down),
+        ] {
+            for word in words {
+                assert_eq!(ok(word), Command::Go(direction));
+                assert_eq!(ok(&word.to_uppercase()), Command::Go(direction));
+                assert_eq!(ok(&format!("go {}", word)), Command::Go(direction));
+            }
+        }
+        assert_eq!(
+            parse("go sideways"),
+            Err(ParseError::UnknownDirection("sideways".to_string()))
+        );
+    }
+
+    #[test]
+    fn at_and_to_are_dropped() {
+        assert_eq!(ok("look at rat"), Command::Examine("rat".to_string()));
+        assert_eq!(ok("x at old key"), Command::Examine("old key".to_string()));
+        assert_eq!(ok("talk to ghost"), Command::Talk("ghost".to_string()));
+        assert_eq!(ok("ask  to   ghost"), Command::Talk("ghost".to_string()));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+        assert_eq!(
+            parse("Dance"),
+            Err(ParseError::UnknownVerb("dance".to_string()))
+        );
+        assert_eq!(
+            parse("dance wildly"),
+            Err(ParseError::UnknownVerb("dance".to_string()))
+        );
+        assert_eq!(
+            parse("n now"),
+            Err(ParseError::UnknownVerb("n".to_string()))
+        );
+        for (line, question) in [
+            ("go", "Go where?"),
+            ("take", "Take what?"),
+            ("drop", "Drop what?"),
+            ("equip", "Equip what?"),
+            ("use", "Use what?"),
+            ("say", "Say what?"),
+            ("talk", "Talk to whom?"),
+            ("answer", "Answer what?"),
+        ] {
+            assert_eq!(parse(line), Err(ParseError::MissingArgument(question)));
+        }
+        assert_eq!(
+            parse("dance").unwrap_err().to_string(),
+            "I don't know how to 'dance'. Type 'help'."
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! # mud_game_app::render
+//! Ways to show a [`View`]: [`St7789Renderer`] draws the game panel on the
+//! display, [`TextRenderer`] keeps a text status line for boards without one
+//! (or for the host).
+//!
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::Text,
+};
+
+use super::engine::View;
+
+pub const LINE_HEIGHT: i32 = 15;
+const MAX_MESSAGE_LINES: usize = 5;
+const MAX_DESCRIPTION_LINES: usize = 4;
+/// Left/right margin on the display.
+const MARGIN: i32 = 10;
+const CHAR_WIDTH: u32 = 6;
+
+/// Shows the game to the player.
+pub trait Renderer {
+    /// Replaces whatever was shown before with `view`.
+    fn render(&mut self, view: &View) -> Result<()>;
+}
+
+/// Splits `text` into lines of at most `columns` characters, breaking at spaces.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > columns {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The last [`MAX_MESSAGE_LINES`] lines of the wrapped message log.
+fn message_lines(view: &View, columns: usize) -> Vec<String> {
+    let lines: Vec<String> = view
+        .messages
+        .iter()
+        .flat_map(|msg| wrap(msg, columns))
+        .collect();
+    let first = lines.len().saturating_sub(MAX_MESSAGE_LINES);
+    lines[first..].to_vec()
+}
+
+/// Draws the game panel with `FONT_6X10`: stats, the room and the message log.
+/// Works on the ST7789 from [`crate::board::init_display`], or any other
+/// `Rgb565` draw target.
+pub struct St7789Renderer<D> {
+    display: D,
+    /// Characters that fit between the margins.
+    columns: usize,
+}
+
+impl<D> St7789Renderer<D>
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: core::fmt::Debug,
+{
+    pub fn new(display: D) -> Self {
+        let width = display.bounding_box().size.width;
+        let columns = (width.saturating_sub(2 * MARGIN as u32) / CHAR_WIDTH) as usize;
+        Self { display, columns }
+    }
+
+    fn text(&mut self, text: &str, y: i32, style: MonoTextStyle<'_, Rgb565>) -> Result<()> {
+        Text::new(text, Point::new(MARGIN, y), style)
+            .draw(&mut self.display)
+            .map_err(|e| anyhow!("display error: {:?}", e))?;
+        Ok(())
+    }
+}
+
+impl<D> Renderer for St7789Renderer<D>
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: core::fmt::Debug,
+{
+    fn render(&mut self, view: &View) -> Result<()> {
+        let header_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_RED);
+        let text_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_WHITE);
+        let alert_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_YELLOW);
+
+        self.display
+            .clear(Rgb565::BLACK)
+            .map_err(|e| anyhow!("display error: {:?}", e))?;
+
+        // Player stats
+        self.text(
+            &format!("Health: {}/{}", view.health, view.max_health),
+            10,
+            text_style,
+        )?;
+        self.text(
+            &format!("Level:  {}  XP {}/{}", view.level, view.xp, view.xp_needed),
+            25,
+            text_style,
+        )?;
+
+        // The current room
+        let mut y = 50;
+        self.text(&view.room_name, y, header_style)?;
+        y += LINE_HEIGHT;
+        for line in wrap(&view.room_description, self.columns)
+            .iter()
+            .take(MAX_DESCRIPTION_LINES)
+        {
+            self.text(line, y, text_style)?;
+            y += LINE_HEIGHT;
+        }
+        if let Some(contents) = &view.contents {
+            for line in wrap(contents, self.columns) {
+                self.text(&line, y, text_style)?;
+                y += LINE_HEIGHT;
+            }
+        }
+        self.text(&view.exits, y, text_style)?;
+        y += LINE_HEIGHT + 10;
+
+        // Message log
+        self.text("Messages:", y, header_style)?;
+        y += LINE_HEIGHT;
+        for line in message_lines(view, self.columns) {
+            self.text(&line, y, alert_style)?;
+            y += LINE_HEIGHT;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a status line, MUD-prompt style, e.g. on the console of a board
+/// without a display. Rooms and messages are already in the game's console
+/// output, so only the stats and location are shown, and only when they change.
+pub struct TextRenderer<W> {
+    out: W,
+    last: String,
+}
+
+impl<W: Write> TextRenderer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            last: String::new(),
+        }
+    }
+}
+
+impl<W: Write> Renderer for TextRenderer<W> {
+    fn render(&mut self, view: &View) -> Result<()> {
+        let line = format!(
+            "[{}/{} hp  level {}  {}/{} xp  {}]",
+            view.health, view.max_health, view.level, view.xp, view.xp_needed, view.room_name
+        );
+        if line != self.last {
+            writeln!(self.out, "{}", line)?;
+            self.out.flush()?;
+            self.last = line;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(health: i32, room: &str) -> View {
+        View {
+            health,
+            max_health: 100,
+            level: 1,
+            xp: 0,
+            xp_needed: 50,
+            room_name: room.to_string(),
+            room_description: String::new(),
+            contents: None,
+            exits: "No exits.".to_string(),
+            messages: vec!["Hello.".to_string()],
+        }
+    }
+
+    #[test]
+    fn text_renderer_prints_only_when_the_line_changes() {
+        let mut out = Vec::new();
+        let mut renderer = TextRenderer::new(&mut out);
+        renderer.render(&view(100, "Hall")).unwrap();
+        renderer.render(&view(100, "Hall")).unwrap();
+        renderer.render(&view(99, "Hall")).unwrap();
+        renderer.render(&view(99, "Hall")).unwrap();
+        renderer.render(&view(99, "Cellar")).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[100/100 hp  level 1  0/50 xp  Hall]\n\
+             [99/100 hp  level 1  0/50 xp  Hall]\n\
+             [99/100 hp  level 1  0/50 xp  Cellar]\n"
+        );
+    }
+
+    #[test]
+    fn text_renderer_ignores_messages() {
+        let mut out = Vec::new();
+        let mut renderer = TextRenderer::new(&mut out);
+        let mut second = view(100, "Hall");
+        second.messages.push("Something new.".to_string());
+        renderer.render(&view(100, "Hall")).unwrap();
+        renderer.render(&second).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn wrap_breaks_at_spaces() {
+        assert_eq!(
+            wrap("a pale woman in a burial shroud", 10),
+            ["a pale", "woman in a", "burial", "shroud"]
+        );
+        assert!(wrap("", 10).is_empty());
+    }
+}
@@ -0,0 +1,398 @@
+//! # mud_game_app::save
+//! Saved games as versioned text, one fact per line:
+//!
+//! ```text
+//! mud-save 1
+//! room hall
+//! player 95 2 30
+//! weapon sword
+//! carry torch
+//! carry sword
+//! floor shrine potion
+//! monster rat 0 12
+//! npc ghost shrine
+//! fired lake-first-visit
+//! ```
+//!
+//! `player` holds health, level and XP; `monster` holds health and turns until
+//! respawn; `npc` is where an NPC has wandered to; `floor` lists the items lying
+//! in a room. Everything is referred to by its id in the world file, so a save
+//! still loads after the world is edited: ids that no longer exist are skipped,
+//! except the player's room, and NPCs missing from the save stay where they are.
+//!
+//! A save holds one player and the world as they left it, which suits the
+//! single-player game; the multiplayer server doesn't save.
+//!
+
+use std::fmt;
+
+use super::game::{GameState, MonsterState, PlayerId};
+
+/// Format version written by [`to_text`]. Bump it when a line changes meaning.
+pub const VERSION: u32 = 1;
+const MAGIC: &str = "mud-save";
+
+/// Why a saved game could not be restored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveError {
+    /// 1-based line in the save, or 0 for problems with the save as a whole.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+fn error(line: usize, message: impl Into<String>) -> SaveError {
+    SaveError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Serializes everything about player `id` and the world around them that should
+/// survive a reboot. The message log and the random number generator are left out.
+pub fn to_text(state: &GameState, id: PlayerId) -> String {
+    let world = &state.world;
+    let player = state.player(id);
+    let mut lines = vec![
+        format!("{} {}", MAGIC, VERSION),
+        format!("room {}", world.room(player.room).id),
+        format!("player {} {} {}", player.health, player.level, player.xp),
+    ];
+    if let Some(item) = player.weapon {
+        lines.push(format!("weapon {}", world.item(item).id));
+    }
+    if let Some(item) = player.armor {
+        lines.push(format!("armor {}", world.item(item).id));
+    }
+    for item in &player.inventory {
+        lines.push(format!("carry {}", world.item(*item).id));
+    }
+    for room in world.rooms.iter().filter(|room| !room.items.is_empty()) {
+        let items: Vec<&str> = room
+            .items
+            .iter()
+            .map(|item| world.item(*item).id.as_str())
+            .collect();
+        lines.push(format!("floor {} {}", room.id, items.join(" ")));
+    }
+    for (monster, monster_state) in world.monsters.iter().zip(&state.monsters) {
+        lines.push(format!(
+            "monster {} {} {}",
+            monster.id, monster_state.health, monster_state.respawn_in
+        ));
+    }
+    for (npc, room) in world.npcs.iter().zip(&state.npc_rooms) {
+        lines.push(format!("npc {} {}", npc.id, world.room(*room).id));
+    }
+    for (event, fired) in world.events.iter().zip(&player.fired) {
+        if *fired {
+            lines.push(format!("fired {}", event.id));
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+fn number<T: std::str::FromStr>(line: usize, value: &str) -> Result<T, SaveError> {
+    value
+        .parse()
+        .map_err(|_| error(line, format!("invalid number '{}'", value)))
+}
+
+/// Restores a game saved with [`to_text`] into `state` as player `id`. `state`
+/// must use the same world file; the player is shown the room they are back in.
+/// On error `state` is left unchanged.
+pub fn restore(state: &mut GameState, id: PlayerId, text: &str) -> Result<(), SaveError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+    match lines.next().map(|(_, header)| header.split_once(' ')) {
+        Some(Some((MAGIC, version))) => {
+            let version: u32 = number(1, version)?;
+            if version != VERSION {
+                return Err(error(1, format!("unsupported save version {}", version)));
+            }
+        }
+        _ => return Err(error(1, "not a saved game")),
+    }
+
+    let world = &state.world;
+    let mut room = None;
+    let mut player = state.player(id).clone();
+    player.weapon = None;
+    player.armor = None;
+    player.inventory.clear();
+    player.fired = vec![false; world.events.len()];
+    let mut floors = vec![Vec::new(); world.rooms.len()];
+    let mut monsters: Vec<MonsterState> = state.monsters.clone();
+    let mut npc_rooms = state.npc_rooms.clone();
+
+    for (line, text) in lines {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["room", id] => {
+                room = Some(
+                    world
+                        .room_by_id(id)
+                        .ok_or_else(|| error(line, format!("unknown room '{}'", id)))?,
+                )
+            }
+            ["player", health, level, xp] => {
+                player.health = number(line, health)?;
+                player.level = number(line, level)?;
+                player.xp = number(line, xp)?;
+            }
+            ["weapon", id] => player.weapon = world.item_by_id(id),
+            ["armor", id] => player.armor = world.item_by_id(id),
+            ["carry", id] => player.inventory.extend(world.item_by_id(id)),
+            ["floor", room, items @ ..] => {
+                if let Some(room) = world.room_by_id(room) {
+                    floors[room].extend(items.iter().filter_map(|id| world.item_by_id(id)));
+                }
+            }
+            ["monster", id, health, respawn_in] => {
+                if let Some(monster) = world.monster_by_id(id) {
+                    monsters[monster] = MonsterState {
+                        health: number(line, health)?,
+                        respawn_in: number(line, respawn_in)?,
+                    };
+                }
+            }
+            ["npc", id, room] => {
+                if let (Some(npc), Some(room)) = (world.npc_by_id(id), world.room_by_id(room)) {
+                    npc_rooms[npc] = room;
+                }
+            }
+            ["fired", id] => {
+                if let Some(event) = world.event_by_id(id) {
+                    player.fired[event] = true;
+                }
+            }
+            _ => return Err(error(line, format!("unexpected '{}'", text))),
+        }
+    }
+
+    let room = room.ok_or_else(|| error(0, "the save has no room"))?;
+    if player.level < 1 || player.health < 1 {
+        return Err(error(0, "invalid player stats"));
+    }
+    // Equipment the player no longer carries (e.g. removed from the world) is dropped.
+    for slot in [&mut player.weapon, &mut player.armor] {
+        if slot.is_some_and(|item| !player.inventory.contains(&item)) {
+            *slot = None;
+        }
+    }
+    player.health = player.health.min(player.max_health());
+
+    for (room, items) in state.world.rooms.iter_mut().zip(floors) {
+        room.items = items;
+    }
+    player.room = room;
+    *state.player_mut(id) = player;
+    state.monsters = monsters;
+    state.npc_rooms = npc_rooms;
+    state.look(id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::game::Player;
+    use super::super::world::{ItemId, RoomId, World};
+    use super::*;
+
+    fn game() -> (GameState, PlayerId) {
+        let mut state = GameState::new(World::builtin(), 1);
+        let id = state.join("Ann");
+        state.take_output(id);
+        (state, id)
+    }
+
+    /// Everything [`restore`] may change.
+    fn snapshot(
+        state: &GameState,
+        id: PlayerId,
+    ) -> (Player, Vec<Vec<ItemId>>, Vec<MonsterState>, Vec<RoomId>) {
+        (
+            state.player(id).clone(),
+            state
+                .world
+                .rooms
+                .iter()
+                .map(|room| room.items.clone())
+                .collect(),
+            state.monsters.clone(),
+            state.npc_rooms.clone(),
+        )
+    }
+
+    fn save(lines: &[&str]) -> String {
+        format!("{} {}\n{}\n", MAGIC, VERSION, lines.join("\n"))
+    }
+
+    #[test]
+    fn round_trip() {
+        let (mut state, id) = game();
+        for command in [
+            "take torch",
+            "north",
+            "take sword",
+            "wield sword",
+            "drop torch",
+        ] {
+            state.handle_command(id, command);
+        }
+        let world = &state.world;
+        let (rat, digger) = (
+            world.monster_by_id("rat").unwrap(),
+            world.npc_by_id("digger").unwrap(),
+        );
+        let (lake_event, entrance) = (
+            world.event_by_id("lake-first-visit").unwrap(),
+            world.room_by_id("entrance").unwrap(),
+        );
+        state.monsters[rat] = MonsterState {
+            health: 0,
+            respawn_in: 7,
+        };
+        state.npc_rooms[digger] = entrance;
+        let player = state.player_mut(id);
+        (player.health, player.level, player.xp) = (95, 2, 30);
+        player.fired[lake_event] = true;
+        let text = to_text(&state, id);
+
+        let (mut restored, other) = game();
+        restore(&mut restored, other, &text).unwrap();
+
+        let (player, floors, monsters, npc_rooms) = snapshot(&state, id);
+        let (back, back_floors, back_monsters, back_npc_rooms) = snapshot(&restored, other);
+        assert_eq!(back.room, state.world.room_by_id("hall").unwrap());
+        assert_eq!(
+            (back.health, back.level, back.xp),
+            (player.health, player.level, player.xp)
+        );
+        assert_eq!(back.inventory, player.inventory);
+        assert_eq!(back.weapon, state.world.item_by_id("sword"));
+        assert_eq!((back.weapon, back.armor), (player.weapon, player.armor));
+        assert_eq!(back.fired, player.fired);
+        assert_eq!(back_floors, floors);
+        assert_eq!(back_monsters, monsters);
+        assert_eq!(back_npc_rooms, npc_rooms);
+        assert_eq!(to_text(&restored, other), text);
+        assert!(restored
+            .take_output(other)
+            .contains(&"== Hall of Bones ==".to_string()));
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        let (mut state, id) = game();
+        for (text, message) in [
+            ("", "not a saved game"),
+            ("hello\nroom hall\n", "not a saved game"),
+            ("mud-save\n", "not a saved game"),
+            ("mud-save one\n", "invalid number 'one'"),
+            ("mud-save 2\nroom hall\n", "unsupported save version 2"),
+        ] {
+            assert_eq!(restore(&mut state, id, text), Err(error(1, message)));
+        }
+    }
+
+    #[test]
+    fn unknown_room_is_an_error() {
+        let (mut state, id) = game();
+        assert_eq!(
+            restore(&mut state, id, &save(&["player 50 1 0", "room attic"])),
+            Err(error(3, "unknown room 'attic'"))
+        );
+        assert_eq!(
+            restore(&mut state, id, &save(&["player 50 1 0"])),
+            Err(error(0, "the save has no room"))
+        );
+    }
+
+    #[test]
+    fn unknown_items_monsters_and_npcs_are_skipped() {
+        let (mut state, id) = game();
+        let text = save(&[
+            "room hall",
+            "player 50 1 0",
+            "weapon crown",
+            "carry crown",
+            "carry torch",
+            "floor hall crown key",
+            "floor attic sword",
+            "monster dragon 5 0",
+            "npc bard hall",
+            "fired nothing",
+        ]);
+        let monsters = state.monsters.clone();
+        let npc_rooms = state.npc_rooms.clone();
+        restore(&mut state, id, &text).unwrap();
+
+        let world = &state.world;
+        let player = state.player(id);
+        assert_eq!(player.inventory, [world.item_by_id("torch").unwrap()]);
+        assert_eq!(player.weapon, None);
+        assert!(player.fired.iter().all(|fired| !fired));
+        let hall = world.room_by_id("hall").unwrap();
+        assert_eq!(world.room(hall).items, [world.item_by_id("key").unwrap()]);
+        let others = world.rooms.iter().filter(|room| room.id != "hall");
+        assert!(others.into_iter().all(|room| room.items.is_empty()));
+        assert_eq!(state.monsters, monsters);
+        assert_eq!(state.npc_rooms, npc_rooms);
+    }
+
+    #[test]
+    fn errors_leave_the_game_unchanged() {
+        let (mut state, id) = game();
+        state.handle_command(id, "take torch");
+        state.take_output(id);
+        let before = snapshot(&state, id);
+        for text in [
+            save(&[
+                "room lake",
+                "player 50 3 0",
+                "carry key",
+                "monster rat 0 5",
+                "bogus",
+            ]),
+            save(&["room lake", "player 50 x 0"]),
+            save(&["room lake", "player 0 1 0"]),
+            save(&["floor lake torch", "npc ghost lake"]),
+        ] {
+            assert!(restore(&mut state, id, &text).is_err(), "{}", text);
+            assert_eq!(snapshot(&state, id), before, "{}", text);
+        }
+    }
+
+    #[test]
+    fn equipment_not_carried_is_cleared() {
+        let (mut state, id) = game();
+        let text = save(&[
+            "room hall",
+            "player 50 1 0",
+            "weapon sword",
+            "armor mail",
+            "carry mail",
+        ]);
+        restore(&mut state, id, &text).unwrap();
+        let player = state.player(id);
+        assert_eq!(player.weapon, None);
+        assert_eq!(player.armor, state.world.item_by_id("mail"));
+    }
+}
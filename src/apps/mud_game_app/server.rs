@@ -0,0 +1,413 @@
+//! # mud_game_app::server
+//! Multiplayer MUD over TCP: every telnet (or `nc`) client plays their own
+//! character in one shared [`GameState`].
+//!
+//! Each connection gets a thread that reads lines and runs them against the game
+//! under a lock; whatever that produces for any player is queued right away for
+//! that player's writer thread, so players see each other move and talk, and a
+//! client that stops reading only holds up its own output. Only `std::net` and the
+//! telnet input handling from [`crate::shell::telnet`] are used, so [`Server`]
+//! runs unchanged on the host against loopback sockets.
+//!
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::{info, warn};
+
+use super::game::{GameState, PlayerId};
+use crate::shell::telnet::Connection;
+
+pub const DEFAULT_PORT: u16 = 4000;
+/// Further connections are turned away while this many are open.
+pub const MAX_PLAYERS: usize = 4;
+const SESSION_STACK_SIZE: usize = 8192;
+/// Writer threads only copy text to a socket.
+const WRITER_STACK_SIZE: usize = 4096;
+const MAX_NAME_LEN: usize = 12;
+const MAX_LINE_LEN: usize = 200;
+/// A client that stops reading is dropped instead of stalling everyone else.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The shared game plus a way to reach every connected player.
+pub struct Server {
+    game: Mutex<GameState>,
+    /// Where to send each player's output (see [`write_output`]). Always locked
+    /// after `game`.
+    clients: Mutex<BTreeMap<PlayerId, Sender<String>>>,
+    /// Connections open, including those still choosing a name.
+    active: AtomicUsize,
+}
+
+impl Server {
+    /// `game` should not have any players yet.
+    pub fn new(game: GameState) -> Arc<Self> {
+        Arc::new(Self {
+            game: Mutex::new(game),
+            clients: Mutex::new(BTreeMap::new()),
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// The game, e.g. for a status display. Don't hold on to it: sessions wait
+    /// for it on every command.
+    pub fn game(&self) -> MutexGuard<'_, GameState> {
+        lock(&self.game)
+    }
+
+    /// Accepts connections on `listener` forever, serving each in its own thread.
+    pub fn serve(self: &Arc<Self>, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("MUD accept failed: {}", e);
+                    continue;
+                }
+            };
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+            if self.active.load(Ordering::SeqCst) >= MAX_PLAYERS {
+                info!("MUD: refusing {}, the game is full", peer);
+                let _ = stream.write_all(b"The game is full, try again later.\r\n");
+                continue;
+            }
+
+            info!("MUD: connection from {}", peer);
+            self.active.fetch_add(1, Ordering::SeqCst);
+            let server = self.clone();
+            let spawned = thread::Builder::new()
+                .stack_size(SESSION_STACK_SIZE)
+                .spawn(move || {
+                    if let Err(e) = server.session(stream) {
+                        info!("MUD: session from {} failed: {}", peer, e);
+                    }
+                    info!("MUD: {} disconnected", peer);
+                    server.active.fetch_sub(1, Ordering::SeqCst);
+                });
+            if let Err(e) = spawned {
+                warn!("MUD: could not start session: {}", e);
+                self.active.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        Ok(())
+    }
+
+    /// Advances the game by one turn and sends out what happened.
+    pub fn tick(&self) {
+        let mut game = self.game();
+        game.simulate_turn();
+        self.flush(&mut game);
+    }
+
+    /// Queues every player's pending output for their writer thread. Nothing
+    /// here waits for a socket, so the game is never locked while writing.
+    fn flush(&self, game: &mut GameState) {
+        let ids: Vec<PlayerId> = game.players().map(|(id, _)| id).collect();
+        let clients = lock(&self.clients);
+        for id in ids {
+            let lines = game.take_output(id);
+            let Some(output) = clients.get(&id) else {
+                continue;
+            };
+            let mut text = String::new();
+            for line in lines {
+                text.push_str(&line);
+                text.push_str("\r\n");
+            }
+            if !text.is_empty() {
+                // A writer that gave up has shut the socket, which ends the session.
+                let _ = output.send(text);
+            }
+        }
+    }
+
+    /// Runs one client on `stream` from choosing a name until they quit or disconnect.
+    fn session(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let writer = stream.try_clone()?;
+        let (output, queued) = mpsc::channel();
+        thread::Builder::new()
+            .stack_size(WRITER_STACK_SIZE)
+            .spawn(move || write_output(writer, queued))?;
+        let mut conn = Connection::new(stream);
+        conn.stream.write_all(b"Welcome to the MUD.\r\n")?;
+
+        let Some(id) = self.join(&mut conn, &output)? else {
+            return Ok(());
+        };
+        let result = self.play(&mut conn, id, &output);
+
+        let mut game = self.game();
+        info!("MUD: {} left", game.player(id).name);
+        game.leave(id);
+        lock(&self.clients).remove(&id);
+        self.flush(&mut game);
+        result
+    }
+
+    /// Asks for a name until the client picks a valid one that isn't taken, then
+    /// adds their player to the game with `output` for what they should see.
+    fn join(
+        &self,
+        conn: &mut Connection<TcpStream>,
+        output: &Sender<String>,
+    ) -> io::Result<Option<PlayerId>> {
+        loop {
+            conn.stream.write_all(b"What is your name? ")?;
+            let Some(name) = read_line(conn)? else {
+                return Ok(None);
+            };
+            let name = name.trim();
+            let valid = !name.is_empty()
+                && name.len() <= MAX_NAME_LEN
+                && name.chars().all(|c| c.is_ascii_alphanumeric());
+            if !valid {
+                write!(
+                    conn.stream,
+                    "Names are 1 to {} letters or digits.\r\n",
+                    MAX_NAME_LEN
+                )?;
+                continue;
+            }
+
+            let mut game = self.game();
+            let taken = game
+                .players()
+                .any(|(_, player)| player.name.eq_ignore_ascii_case(name));
+            if taken {
+                drop(game);
+                conn.stream
+                    .write_all(b"Someone by that name is already here.\r\n")?;
+                continue;
+            }
+            let id = game.join(name);
+            lock(&self.clients).insert(id, output.clone());
+            self.flush(&mut game);
+            info!("MUD: {} joined", name);
+            return Ok(Some(id));
+        }
+    }
+
+    /// Runs commands from player `id` until they type `quit` or disconnect.
+    fn play(
+        &self,
+        conn: &mut Connection<TcpStream>,
+        id: PlayerId,
+        output: &Sender<String>,
+    ) -> io::Result<()> {
+        while let Some(line) = read_line(conn)? {
+            let input = line.trim();
+            if input.is_empty() {
+                continue;
+            }
+            if input.eq_ignore_ascii_case("quit") {
+                // After anything still queued for this player.
+                let _ = output.send("Bye.\r\n".to_string());
+                break;
+            }
+            let mut game = self.game();
+            if game.handle_command(id, input).is_some() {
+                game.add_message(id, "The server doesn't save games.");
+            }
+            self.flush(&mut game);
+        }
+        Ok(())
+    }
+}
+
+/// Writes what is sent on `queued` to `stream` until every sender is gone. A write
+/// that fails (or times out) shuts the socket, so the session's next read fails.
+fn write_output(mut stream: TcpStream, queued: Receiver<String>) {
+    for text in queued {
+        if let Err(e) = stream.write_all(text.as_bytes()) {
+            info!("MUD: could not write to a player: {}", e);
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+/// Reads a line, leaving editing to the client (telnet's line mode, `nc`).
+/// `None` if the client disconnects or presses Ctrl-C/Ctrl-D.
+fn read_line(conn: &mut Connection<TcpStream>) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    while let Some(byte) = conn.next_byte()? {
+        match byte {
+            b'\r' | b'\n' => return Ok(Some(line)),
+            CTRL_C | CTRL_D => return Ok(None),
+            0x08 | 0x7f => {
+                line.pop();
+            }
+            byte if (byte.is_ascii_graphic() || byte == b' ') && line.len() < MAX_LINE_LEN => {
+                line.push(byte as char)
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::SocketAddr;
+
+    use super::super::world::World;
+    use super::*;
+
+    /// Starts a server with the built-in world on a free loopback port.
+    fn start() -> (Arc<Server>, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::new(GameState::new(World::builtin(), 1));
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+        (server, addr)
+    }
+
+    struct Client {
+        stream: TcpStream,
+        /// Received but not yet expected.
+        pending: String,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            Self {
+                stream,
+                pending: String::new(),
+            }
+        }
+
+        /// Connects and plays as `name`.
+        fn join(addr: SocketAddr, name: &str) -> Self {
+            let mut client = Self::connect(addr);
+            client.expect("What is your name? ");
+            client.send(name);
+            client.expect("Exits:");
+            client
+        }
+
+        fn send(&mut self, line: &str) {
+            write!(self.stream, "{}\r\n", line).unwrap();
+        }
+
+        /// Reads until `text` arrives and returns everything up to and including it.
+        fn expect(&mut self, text: &str) -> String {
+            loop {
+                if let Some(at) = self.pending.find(text) {
+                    let end = at + text.len();
+                    return self.pending.drain(..end).collect();
+                }
+                let mut buf = [0; 256];
+                match self.stream.read(&mut buf) {
+                    Ok(0) => panic!("closed while waiting for {:?}: {:?}", text, self.pending),
+                    Ok(len) => self.pending.push_str(&String::from_utf8_lossy(&buf[..len])),
+                    Err(e) => panic!("{} while waiting for {:?}: {:?}", e, text, self.pending),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn names_must_be_valid_and_free() {
+        let (_server, addr) = start();
+        let mut ann = Client::connect(addr);
+        ann.expect("Welcome to the MUD.\r\nWhat is your name? ");
+        for bad in ["", "two words", "Aaaaaaaaaaaaa", "x!"] {
+            ann.send(bad);
+            ann.expect("Names are 1 to 12 letters or digits.\r\nWhat is your name? ");
+        }
+        ann.send("Ann");
+        ann.expect("You enter the ancient crypt...");
+
+        let mut bob = Client::connect(addr);
+        bob.expect("What is your name? ");
+        bob.send("ANN");
+        bob.expect("Someone by that name is already here.\r\nWhat is your name? ");
+        bob.send("Bob");
+        bob.expect("Exits:");
+        ann.expect("Bob has joined the game.\r\n");
+    }
+
+    #[test]
+    fn say_reaches_the_other_player_and_who_lists_both() {
+        let (_server, addr) = start();
+        let mut ann = Client::join(addr, "Ann");
+        let mut bob = Client::join(addr, "Bob");
+        ann.expect("Bob has joined the game.\r\n");
+
+        bob.send("say hello there");
+        bob.expect("You say: hello there\r\n");
+        ann.expect("Bob says: hello there\r\n");
+
+        ann.send("who");
+        ann.expect("Playing: Ann (Crypt Entrance), Bob (Crypt Entrance).\r\n");
+
+        bob.send("save");
+        bob.expect("The server doesn't save games.\r\n");
+        bob.send("quit");
+        bob.expect("Bye.\r\n");
+        ann.expect("Bob has left the game.\r\n");
+    }
+
+    #[test]
+    fn refuses_players_beyond_the_limit() {
+        let (server, addr) = start();
+        let clients: Vec<Client> = (0..MAX_PLAYERS)
+            .map(|_| {
+                let mut client = Client::connect(addr);
+                client.expect("What is your name? ");
+                client
+            })
+            .collect();
+
+        let mut late = Client::connect(addr);
+        late.expect("The game is full, try again later.\r\n");
+        let mut rest = [0; 16];
+        assert_eq!(late.stream.read(&mut rest).unwrap(), 0);
+        assert_eq!(server.active.load(Ordering::SeqCst), MAX_PLAYERS);
+        drop(clients);
+    }
+
+    #[test]
+    fn disconnecting_leaves_items_behind() {
+        let (server, addr) = start();
+        let mut ann = Client::join(addr, "Ann");
+        let mut bob = Client::join(addr, "Bob");
+        ann.send("take torch");
+        ann.expect("You take the torch.\r\n");
+        bob.expect("Ann takes the torch.\r\n");
+
+        drop(ann);
+        bob.expect("Ann has left the game.\r\n");
+        bob.send("look");
+        bob.expect("You see: torch.\r\n");
+
+        let game = server.game();
+        assert_eq!(game.players().count(), 1);
+        let entrance = game.world.room_by_id("entrance").unwrap();
+        let torch = game.world.item_by_id("torch").unwrap();
+        assert!(game.world.room(entrance).items.contains(&torch));
+    }
+}
@@ -0,0 +1,950 @@
+//! # mud_game_app::world
+//! The MUD world: rooms joined by exits, items, NPCs, monsters and scripted events,
+//! read from a small text format.
+//!
+//! A world file is a list of sections. Lines starting with `#` are comments, and
+//! every other line is a key followed by its value:
+//!
+//! ```text
+//! start entrance
+//! intro You enter the ancient crypt...
+//!
+//! [room entrance]
+//! name Crypt Entrance
+//! desc You stand in a dark chamber.
+//! exit north hall
+//! item torch
+//!
+//! [item torch]
+//! name torch
+//! desc A pitch-soaked torch.
+//! use The torch flickers.
+//! heal 0
+//!
+//! [item sword]
+//! name rusty sword
+//! slot weapon
+//! attack 3
+//!
+//! [npc ghost]
+//! name Weeping Ghost
+//! desc A pale woman in a burial shroud.
+//! room entrance
+//! wander 10
+//! talk start She looks up. "Have you seen my ring?"
+//! answer start ring Where did you lose it?
+//! answer start bye Leave her be.
+//! talk ring "By the water. It is cold there."
+//! talk bye She goes back to weeping.
+//!
+//! [monster rat]
+//! name giant rat
+//! room entrance
+//! health 12
+//! attack 3
+//! defense 1
+//! xp 10
+//! respawn 20
+//!
+//! [event chill]
+//! on enter entrance
+//! once
+//! message A cold draught brushes past you.
+//!
+//! [event whispers]
+//! on timer 15 entrance
+//! message Something whispers your name.
+//! ```
+//!
+//! Section ids are only used inside the file; the game addresses everything by
+//! index. `desc`, `exit`, `item` and `intro` may repeat. Items with a `slot`
+//! (`weapon` or `armor`) can be equipped for their `attack`/`defense` bonus; a
+//! monster with `respawn` comes back that many turns after it is killed.
+//!
+//! An NPC with `wander` moves through a random exit every that many turns. `talk`
+//! lines are its side of a conversation, one per topic; the first one is what it
+//! says when spoken to, and `answer <topic> <next> <text>` offers the player a reply
+//! that leads to topic `<next>`. A topic without answers ends the conversation.
+//!
+//! Events fire `on enter <room>`, `on timer <turns> [room]` (every that many turns,
+//! for players in the room or everywhere) or `on use <item> [room]` (when a player
+//! uses the item, there or anywhere), and show their `message`; with `once` only
+//! the first time for each player.
+//!
+//! [`World::parse`] rejects references to ids (and topics) that don't exist, and
+//! `build.rs` runs it on the built-in world ([`BUILTIN`]) so a dangling exit fails
+//! the build. This module has no dependencies beyond `std` for that reason.
+//!
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// The world compiled into the firmware.
+pub const BUILTIN: &str = include_str!("crypt.txt");
+
+/// Index of a room in [`World::rooms`].
+pub type RoomId = usize;
+/// Index of an item in [`World::items`].
+pub type ItemId = usize;
+/// Index of a monster in [`World::monsters`].
+pub type MonsterId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// Accepts the full name or its first letter (`north`, `n`).
+    pub fn parse(word: &str) -> Option<Self> {
+        match word.to_ascii_lowercase().as_str() {
+            "north" | "n" => Some(Self::North),
+            "east" | "e" => Some(Self::East),
+            "south" | "s" => Some(Self::South),
+            "west" | "w" => Some(Self::West),
+            "up" | "u" => Some(Self::Up),
+            "down" | "d" => Some(Self::Down),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::North => "north",
+            Self::East => "east",
+            Self::South => "south",
+            Self::West => "west",
+            Self::Up => "up",
+            Self::Down => "down",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub exits: Vec<(Direction, RoomId)>,
+    /// Items lying on the floor.
+    pub items: Vec<ItemId>,
+}
+
+impl Room {
+    /// Where the exit in `direction` leads, if there is one.
+    pub fn exit(&self, direction: Direction) -> Option<RoomId> {
+        self.exits
+            .iter()
+            .find(|(dir, _)| *dir == direction)
+            .map(|(_, room)| *room)
+    }
+
+    /// `Exits: north, east` (or `No exits.`), for the room panel.
+    pub fn exits_line(&self) -> String {
+        if self.exits.is_empty() {
+            return "No exits.".to_string();
+        }
+        let names: Vec<&str> = self.exits.iter().map(|(dir, _)| dir.name()).collect();
+        format!("Exits: {}", names.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Shown when the item is used.
+    pub use_message: Option<String>,
+    /// Health restored by using the item, which uses it up. 0 for lasting items.
+    pub heal: i32,
+    /// Where the item is worn, if it can be equipped.
+    pub slot: Option<Slot>,
+    /// Bonuses while equipped.
+    pub attack: i32,
+    pub defense: i32,
+}
+
+/// Equipment slot; the player has one of each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Weapon,
+    Armor,
+}
+
+#[derive(Debug, Clone)]
+pub struct Npc {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Where it starts.
+    pub room: RoomId,
+    /// Turns between moves to a neighbouring room; 0 if it stays put.
+    pub wander: u32,
+    /// What it can talk about, starting with the first topic. Empty if it has
+    /// nothing to say.
+    pub dialogue: Vec<Topic>,
+}
+
+/// One step of a conversation with an [`Npc`].
+#[derive(Debug, Clone)]
+pub struct Topic {
+    pub id: String,
+    /// What the NPC says.
+    pub text: String,
+    /// Replies the player can pick, each with the index in [`Npc::dialogue`] of
+    /// the topic it leads to.
+    pub answers: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Monster {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub room: RoomId,
+    pub health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    /// Experience for killing it.
+    pub xp: i32,
+    /// Turns until it comes back after being killed; 0 for never.
+    pub respawn: u32,
+}
+
+/// What fires an [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// The player walks into the room.
+    Enter(RoomId),
+    /// Every `every` turns, for players in `room`, or for everyone if it's `None`.
+    Timer { every: u32, room: Option<RoomId> },
+    /// The player uses `item`, in `room` if one is given.
+    Use { item: ItemId, room: Option<RoomId> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: String,
+    pub trigger: Trigger,
+    pub message: String,
+    /// Fires only the first time.
+    pub once: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct World {
+    pub rooms: Vec<Room>,
+    pub items: Vec<Item>,
+    pub npcs: Vec<Npc>,
+    pub monsters: Vec<Monster>,
+    pub events: Vec<Event>,
+    /// Where new players appear.
+    pub start: RoomId,
+    /// Messages shown when the game starts.
+    pub intro: Vec<String>,
+}
+
+/// Why a world file was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldError {
+    /// 1-based line in the file, or 0 for problems with the file as a whole.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
+
+fn error(line: usize, message: impl Into<String>) -> WorldError {
+    WorldError {
+        line,
+        message: message.into(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Room,
+    Item,
+    Npc,
+    Monster,
+    Event,
+}
+
+impl Kind {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "room" => Some(Self::Room),
+            "item" => Some(Self::Item),
+            "npc" => Some(Self::Npc),
+            "monster" => Some(Self::Monster),
+            "event" => Some(Self::Event),
+            _ => None,
+        }
+    }
+}
+
+/// A `[kind id]` section before its references are resolved.
+struct Section<'a> {
+    kind: Kind,
+    id: &'a str,
+    line: usize,
+    /// `(line, key, value)` in file order.
+    entries: Vec<(usize, &'a str, &'a str)>,
+}
+
+impl<'a> Section<'a> {
+    fn first(&self, key: &str) -> Option<(usize, &'a str)> {
+        self.entries
+            .iter()
+            .find(|(_, k, _)| *k == key)
+            .map(|(line, _, value)| (*line, *value))
+    }
+
+    fn all(&self, key: &'a str) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        self.entries
+            .iter()
+            .filter(move |(_, k, _)| *k == key)
+            .map(|(line, _, value)| (*line, *value))
+    }
+
+    fn required(&self, key: &str) -> Result<(usize, &'a str), WorldError> {
+        self.first(key).ok_or_else(|| {
+            error(
+                self.line,
+                format!("{} '{}' has no {}", self.kind_name(), self.id, key),
+            )
+        })
+    }
+
+    /// The value of `key` as a number, or `default` if it isn't set.
+    fn number<T: std::str::FromStr>(&self, key: &str, default: T) -> Result<T, WorldError> {
+        match self.first(key) {
+            Some((line, value)) => value
+                .parse()
+                .map_err(|_| error(line, format!("invalid {} '{}'", key, value))),
+            None => Ok(default),
+        }
+    }
+
+    /// Every `desc` line, joined into one paragraph.
+    fn description(&self) -> String {
+        self.all("desc")
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), WorldError> {
+        match self
+            .entries
+            .iter()
+            .find(|(_, key, _)| !allowed.contains(key))
+        {
+            Some((line, key, _)) => Err(error(
+                *line,
+                format!(
+                    "unknown key '{}' in {} '{}'",
+                    key,
+                    self.kind_name(),
+                    self.id
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self.kind {
+            Kind::Room => "room",
+            Kind::Item => "item",
+            Kind::Npc => "npc",
+            Kind::Monster => "monster",
+            Kind::Event => "event",
+        }
+    }
+}
+
+/// Section ids of one kind, mapped to their index.
+struct Ids<'a>(HashMap<&'a str, usize>);
+
+impl<'a> Ids<'a> {
+    fn new(sections: &[Section<'a>], kind: Kind) -> Result<Self, WorldError> {
+        let mut ids = HashMap::new();
+        for section in sections.iter().filter(|section| section.kind == kind) {
+            if ids.insert(section.id, ids.len()).is_some() {
+                return Err(error(
+                    section.line,
+                    format!("duplicate {} '{}'", section.kind_name(), section.id),
+                ));
+            }
+        }
+        Ok(Self(ids))
+    }
+
+    fn resolve(&self, line: usize, what: &str, id: &str) -> Result<usize, WorldError> {
+        self.0
+            .get(id)
+            .copied()
+            .ok_or_else(|| error(line, format!("unknown {} '{}'", what, id)))
+    }
+}
+
+/// Splits `key value...` at the first space.
+fn split_key(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((key, value)) => (key, value.trim()),
+        None => (line, ""),
+    }
+}
+
+impl World {
+    /// The world compiled into the firmware.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("the built-in world is checked by build.rs")
+    }
+
+    /// Reads a world file, resolving and checking every reference in it.
+    pub fn parse(text: &str) -> Result<Self, WorldError> {
+        let mut header = Vec::new();
+        let mut sections: Vec<Section> = Vec::new();
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let trimmed = raw.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if let Some(inner) = trimmed.strip_prefix('[') {
+                let inner = inner
+                    .strip_suffix(']')
+                    .ok_or_else(|| error(line, "missing ] in section header"))?;
+                let (kind, id) = split_key(inner.trim());
+                let kind = Kind::parse(kind).ok_or_else(|| {
+                    error(
+                        line,
+                        format!(
+                            "unknown section '{}' (room, item, npc, monster or event)",
+                            kind
+                        ),
+                    )
+                })?;
+                if id.is_empty() || id.contains(char::is_whitespace) {
+                    return Err(error(line, "section needs a one-word id"));
+                }
+                sections.push(Section {
+                    kind,
+                    id,
+                    line,
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+            let (key, value) = split_key(trimmed);
+            match sections.last_mut() {
+                Some(section) => section.entries.push((line, key, value)),
+                None => header.push((line, key, value)),
+            }
+        }
+
+        let room_ids = Ids::new(&sections, Kind::Room)?;
+        let item_ids = Ids::new(&sections, Kind::Item)?;
+        Ids::new(&sections, Kind::Npc)?;
+        Ids::new(&sections, Kind::Monster)?;
+        Ids::new(&sections, Kind::Event)?;
+
+        let mut world = World {
+            rooms: Vec::new(),
+            items: Vec::new(),
+            npcs: Vec::new(),
+            monsters: Vec::new(),
+            events: Vec::new(),
+            start: 0,
+            intro: Vec::new(),
+        };
+
+        let mut start = None;
+        for (line, key, value) in header {
+            match key {
+                "start" => start = Some(room_ids.resolve(line, "room", value)?),
+                "intro" => world.intro.push(value.to_string()),
+                _ => return Err(error(line, format!("unknown key '{}'", key))),
+            }
+        }
+
+        for section in &sections {
+            match section.kind {
+                Kind::Room => {
+                    section.check_keys(&["name", "desc", "exit", "item"])?;
+                    let mut exits: Vec<(Direction, RoomId)> = Vec::new();
+                    for (line, value) in section.all("exit") {
+                        let (direction, target) = split_key(value);
+                        let direction = Direction::parse(direction).ok_or_else(|| {
+                            error(line, format!("'{}' is not a direction", direction))
+                        })?;
+                        if exits.iter().any(|(dir, _)| *dir == direction) {
+                            return Err(error(
+                                line,
+                                format!("duplicate exit {}", direction.name()),
+                            ));
+                        }
+                        exits.push((direction, room_ids.resolve(line, "room", target)?));
+                    }
+                    let items = section
+                        .all("item")
+                        .map(|(line, id)| item_ids.resolve(line, "item", id))
+                        .collect::<Result<_, _>>()?;
+                    world.rooms.push(Room {
+                        id: section.id.to_string(),
+                        name: section.required("name")?.1.to_string(),
+                        description: section.description(),
+                        exits,
+                        items,
+                    });
+                }
+                Kind::Item => {
+                    section.check_keys(&[
+                        "name", "desc", "use", "heal", "slot", "attack", "defense",
+                    ])?;
+                    let slot = match section.first("slot") {
+                        None => None,
+                        Some((_, "weapon")) => Some(Slot::Weapon),
+                        Some((_, "armor")) => Some(Slot::Armor),
+                        Some((line, other)) => {
+                            return Err(error(
+                                line,
+                                format!("unknown slot '{}' (weapon or armor)", other),
+                            ))
+                        }
+                    };
+                    world.items.push(Item {
+                        id: section.id.to_string(),
+                        name: section
+                            .first("name")
+                            .map_or(section.id, |(_, name)| name)
+                            .to_string(),
+                        description: section.description(),
+                        use_message: section.first("use").map(|(_, text)| text.to_string()),
+                        heal: section.number("heal", 0)?,
+                        slot,
+                        attack: section.number("attack", 0)?,
+                        defense: section.number("defense", 0)?,
+                    });
+                }
+                Kind::Npc => {
+                    section.check_keys(&["name", "desc", "room", "wander", "talk", "answer"])?;
+                    let (line, room) = section.required("room")?;
+                    let mut dialogue: Vec<Topic> = Vec::new();
+                    for (line, value) in section.all("talk") {
+                        let (topic, text) = split_key(value);
+                        if text.is_empty() {
+                            return Err(error(line, "talk needs a topic and what is said"));
+                        }
+                        if dialogue.iter().any(|known| known.id == topic) {
+                            return Err(error(line, format!("duplicate topic '{}'", topic)));
+                        }
+                        dialogue.push(Topic {
+                            id: topic.to_string(),
+                            text: text.to_string(),
+                            answers: Vec::new(),
+                        });
+                    }
+                    for (line, value) in section.all("answer") {
+                        let (topic, rest) = split_key(value);
+                        let (next, text) = split_key(rest);
+                        if text.is_empty() {
+                            return Err(error(
+                                line,
+                                "answer needs a topic, the next topic and what is said",
+                            ));
+                        }
+                        let find = |id: &str| {
+                            dialogue
+                                .iter()
+                                .position(|known| known.id == id)
+                                .ok_or_else(|| error(line, format!("unknown topic '{}'", id)))
+                        };
+                        let (topic, next) = (find(topic)?, find(next)?);
+                        dialogue[topic].answers.push((text.to_string(), next));
+                    }
+                    world.npcs.push(Npc {
+                        id: section.id.to_string(),
+                        name: section.required("name")?.1.to_string(),
+                        description: section.description(),
+                        room: room_ids.resolve(line, "room", room)?,
+                        wander: section.number("wander", 0)?,
+                        dialogue,
+                    });
+                }
+                Kind::Monster => {
+                    section.check_keys(&[
+                        "name", "desc", "room", "health", "attack", "defense", "xp", "respawn",
+                    ])?;
+                    let (line, room) = section.required("room")?;
+                    let health = section.number("health", 10)?;
+                    if health <= 0 {
+                        return Err(error(section.line, "monster health must be positive"));
+                    }
+                    world.monsters.push(Monster {
+                        id: section.id.to_string(),
+                        name: section.required("name")?.1.to_string(),
+                        description: section.description(),
+                        room: room_ids.resolve(line, "room", room)?,
+                        health,
+                        attack: section.number("attack", 1)?,
+                        defense: section.number("defense", 0)?,
+                        xp: section.number("xp", 0)?,
+                        respawn: section.number("respawn", 0)?,
+                    });
+                }
+                Kind::Event => {
+                    section.check_keys(&["on", "message", "once"])?;
+                    let (line, on) = section.required("on")?;
+                    let optional_room = |id: &str| match id {
+                        "" => Ok(None),
+                        id => room_ids.resolve(line, "room", id).map(Some),
+                    };
+                    let trigger = match split_key(on) {
+                        ("enter", room) => Trigger::Enter(room_ids.resolve(line, "room", room)?),
+                        ("timer", rest) => {
+                            let (every, room) = split_key(rest);
+                            let every = every
+                                .parse()
+                                .ok()
+                                .filter(|every| *every > 0)
+                                .ok_or_else(|| error(line, format!("invalid timer '{}'", every)))?;
+                            Trigger::Timer {
+                                every,
+                                room: optional_room(room)?,
+                            }
+                        }
+                        ("use", rest) => {
+                            let (item, room) = split_key(rest);
+                            Trigger::Use {
+                                item: item_ids.resolve(line, "item", item)?,
+                                room: optional_room(room)?,
+                            }
+                        }
+                        _ => {
+                            return Err(error(
+                                line,
+                                format!(
+                                    "unknown trigger '{}' (enter <room>, timer <turns> [room] \
+                                     or use <item> [room])",
+                                    on
+                                ),
+                            ))
+                        }
+                    };
+                    world.events.push(Event {
+                        id: section.id.to_string(),
+                        trigger,
+                        message: section.required("message")?.1.to_string(),
+                        once: section.first("once").is_some(),
+                    });
+                }
+            }
+        }
+
+        if world.rooms.is_empty() {
+            return Err(error(0, "the world has no rooms"));
+        }
+        world.start = start.ok_or_else(|| error(0, "no start room"))?;
+        Ok(world)
+    }
+
+    /// Things that are legal but probably mistakes: exits without a way back and
+    /// rooms that can't be reached from the start.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (id, room) in self.rooms.iter().enumerate() {
+            for (direction, target) in &room.exits {
+                let target = &self.rooms[*target];
+                if !target.exits.iter().any(|(_, back)| *back == id) {
+                    warnings.push(format!(
+                        "exit {} from '{}' to '{}' is one-way",
+                        direction.name(),
+                        room.id,
+                        target.id
+                    ));
+                }
+            }
+        }
+
+        let mut reached = vec![false; self.rooms.len()];
+        let mut pending = vec![self.start];
+        while let Some(room) = pending.pop() {
+            if !std::mem::replace(&mut reached[room], true) {
+                pending.extend(self.rooms[room].exits.iter().map(|(_, target)| *target));
+            }
+        }
+        for (room, reached) in self.rooms.iter().zip(reached) {
+            if !reached {
+                warnings.push(format!(
+                    "room '{}' can't be reached from the start",
+                    room.id
+                ));
+            }
+        }
+        warnings
+    }
+
+    pub fn room(&self, id: RoomId) -> &Room {
+        &self.rooms[id]
+    }
+
+    pub fn room_mut(&mut self, id: RoomId) -> &mut Room {
+        &mut self.rooms[id]
+    }
+
+    pub fn item(&self, id: ItemId) -> &Item {
+        &self.items[id]
+    }
+
+    /// Index of the room with section id `id`.
+    pub fn room_by_id(&self, id: &str) -> Option<RoomId> {
+        self.rooms.iter().position(|room| room.id == id)
+    }
+
+    pub fn item_by_id(&self, id: &str) -> Option<ItemId> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    pub fn monster_by_id(&self, id: &str) -> Option<MonsterId> {
+        self.monsters.iter().position(|monster| monster.id == id)
+    }
+
+    /// Index in [`World::npcs`] of the NPC with section id `id`.
+    pub fn npc_by_id(&self, id: &str) -> Option<usize> {
+        self.npcs.iter().position(|npc| npc.id == id)
+    }
+
+    /// Index in [`World::events`] of the event with section id `id`.
+    pub fn event_by_id(&self, id: &str) -> Option<usize> {
+        self.events.iter().position(|event| event.id == id)
+    }
+
+    /// Finds `name` among `items`: the full name, or one of its words (`sword` for
+    /// `rusty sword`), ignoring case. Returns the position in `items`.
+    pub fn find_item(&self, items: &[ItemId], name: &str) -> Option<usize> {
+        let names: Vec<&str> = items
+            .iter()
+            .map(|id| self.items[*id].name.as_str())
+            .collect();
+        find_name(&names, name)
+    }
+}
+
+/// Position of `name` in `names`: an exact match, else a name containing it as a
+/// word. Case is ignored.
+pub fn find_name(names: &[&str], name: &str) -> Option<usize> {
+    names
+        .iter()
+        .position(|candidate| candidate.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            names.iter().position(|candidate| {
+                candidate
+                    .split_whitespace()
+                    .any(|word| word.eq_ignore_ascii_case(name))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOMS: &str = "
+start hall
+
+[room hall]
+name Hall
+exit north cellar
+
+[room cellar]
+name Cellar
+exit south hall
+";
+
+    /// The error for [`ROOMS`] followed by `extra`.
+    fn error_with(extra: &str) -> WorldError {
+        World::parse(&format!("{}{}", ROOMS, extra)).unwrap_err()
+    }
+
+    fn assert_error(extra: &str, line: usize, message: &str) {
+        assert_eq!(error_with(extra), error(line, message));
+    }
+
+    #[test]
+    fn builtin_world_parses_without_warnings() {
+        let world = World::builtin();
+        assert_eq!(world.room(world.start).id, "entrance");
+        assert_eq!(world.warnings(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn sections_are_resolved_to_indexes() {
+        let world = World::parse(&format!(
+            "{}{}",
+            ROOMS,
+            "
+[item sword]
+slot weapon
+attack 3
+
+[npc ghost]
+name Ghost
+room cellar
+talk start Hello.
+answer start bye Goodbye.
+talk bye Farewell.
+
+[event draught]
+on timer 5 cellar
+once
+message A draught.
+"
+        ))
+        .unwrap();
+        let cellar = world.room_by_id("cellar").unwrap();
+        assert_eq!(world.room(world.start).exit(Direction::North), Some(cellar));
+        let sword = world.item(world.item_by_id("sword").unwrap());
+        assert_eq!(
+            (sword.name.as_str(), sword.slot),
+            ("sword", Some(Slot::Weapon))
+        );
+        assert_eq!(sword.attack, 3);
+        let ghost = &world.npcs[world.npc_by_id("ghost").unwrap()];
+        assert_eq!(ghost.room, cellar);
+        assert_eq!(ghost.dialogue[0].answers, vec![("Goodbye.".to_string(), 1)]);
+        let draught = &world.events[world.event_by_id("draught").unwrap()];
+        assert_eq!(
+            draught.trigger,
+            Trigger::Timer {
+                every: 5,
+                room: Some(cellar)
+            }
+        );
+        assert!(draught.once);
+    }
+
+    #[test]
+    fn dangling_references_are_rejected() {
+        assert_error(
+            "\n[room attic]\nname Attic\nexit down nowhere\n",
+            14,
+            "unknown room 'nowhere'",
+        );
+        assert_error(
+            "\n[room attic]\nname Attic\nitem ghost\n",
+            14,
+            "unknown item 'ghost'",
+        );
+        assert_error(
+            "\n[monster rat]\nname rat\nroom nowhere\n",
+            14,
+            "unknown room 'nowhere'",
+        );
+        assert_error(
+            "\n[event chill]\non use ghost\nmessage Brr.\n",
+            13,
+            "unknown item 'ghost'",
+        );
+        assert_eq!(
+            World::parse("start nowhere\n[room hall]\nname Hall\n").unwrap_err(),
+            error(1, "unknown room 'nowhere'")
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        assert_error(
+            "\n[room hall]\nname Another hall\n",
+            12,
+            "duplicate room 'hall'",
+        );
+        assert_error(
+            "\n[item torch]\n[item torch]\n",
+            13,
+            "duplicate item 'torch'",
+        );
+    }
+
+    #[test]
+    fn duplicate_exits_are_rejected() {
+        assert_error(
+            "\n[room attic]\nname Attic\nexit down hall\nexit d cellar\n",
+            15,
+            "duplicate exit down",
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert_error(
+            "\n[room attic]\nname Attic\nsmell Dust\n",
+            14,
+            "unknown key 'smell' in room 'attic'",
+        );
+        assert_eq!(
+            World::parse(&format!("weather rain\n{}", ROOMS)).unwrap_err(),
+            error(1, "unknown key 'weather'")
+        );
+    }
+
+    #[test]
+    fn answers_must_name_known_topics() {
+        let npc = "\n[npc ghost]\nname Ghost\nroom hall\ntalk start Hello.\n";
+        assert_eq!(
+            error_with(&format!("{}answer start ring Your ring?\n", npc)),
+            error(16, "unknown topic 'ring'")
+        );
+        assert_eq!(
+            error_with(&format!("{}answer ring start Your ring?\n", npc)),
+            error(16, "unknown topic 'ring'")
+        );
+    }
+
+    #[test]
+    fn one_way_exits_and_unreachable_rooms_are_warnings() {
+        let world = World::parse(
+            "
+start hall
+
+[room hall]
+name Hall
+exit down pit
+
+[room pit]
+name Pit
+
+[room attic]
+name Attic
+exit down hall
+",
+        )
+        .unwrap();
+        assert_eq!(
+            world.warnings(),
+            vec![
+                "exit down from 'hall' to 'pit' is one-way",
+                "exit down from 'attic' to 'hall' is one-way",
+                "room 'attic' can't be reached from the start",
+            ]
+        );
+    }
+}
@@ -4,8 +4,7 @@
 //! Displays a 3D rotating cube using embedded-graphics.
 //!
 
-use anyhow::{anyhow, Result};
-use display_interface_spi::SPIInterfaceNoCS;
+use anyhow::Result;
 use embedded_graphics::{
     mono_font::{ascii::FONT_10X20, MonoTextStyle},
     pixelcolor::Rgb565,
@@ -13,61 +12,37 @@ use embedded_graphics::{
     primitives::{Line, PrimitiveStyle},
     text::Text,
 };
-use embedded_hal::spi::MODE_0;
-use esp_idf_hal::prelude::*; // brings in FromValueType for Hz()
-use esp_idf_hal::{
-    delay::FreeRtos,
-    gpio::{AnyIOPin, PinDriver},
-    peripherals::Peripherals,
-    spi::{SpiConfig, SpiDeviceDriver, SpiDriver, SpiDriverConfig},
-};
-use esp_idf_svc::log::EspLogger;
+use esp_idf_hal::{delay::FreeRtos, peripherals::Peripherals};
 use esp_idf_sys as _; // ensure ESP-IDF linkage
-use st7789::{Orientation, ST7789};
 
-// Display constants
-const LCD_WIDTH: u16 = 240;
-const LCD_HEIGHT: u16 = 320;
-const SPI_BAUDRATE_HZ: u32 = 40_000_000; // 40 MHz
+use super::App;
+use crate::board::{self, map_st7789_error};
+
+/// Registry entry for [`run`].
+pub struct RotatingCubeApp;
+
+impl App for RotatingCubeApp {
+    fn name(&self) -> &'static str {
+        "rotating_cube_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Animated wireframe cube"
+    }
 
-fn map_st7789_error<E: core::fmt::Debug>(err: st7789::Error<E>) -> anyhow::Error {
-    anyhow!("ST7789 driver error: {:?}", err)
+    fn run(&self) -> Result<()> {
+        run()
+    }
 }
 
 /// Runs the rotating cube demo:  
 /// draws and animates a wireframe cube.
 pub fn run() -> Result<()> {
-    EspLogger::initialize_default();
     let peripherals = Peripherals::take()?;
-    let sclk = peripherals.pins.gpio14;
-    let mosi = peripherals.pins.gpio13;
-    let miso: Option<AnyIOPin> = None;
-    let cs_pin = peripherals.pins.gpio15;
-    let dc_pin = peripherals.pins.gpio2;
-    let rst_pin = peripherals.pins.gpio0;
-    let bl_pin = peripherals.pins.gpio21;
-
-    let mut backlight = PinDriver::output(bl_pin)?;
-    backlight.set_high()?;
-    let rst = PinDriver::output(rst_pin)?;
-    let dc = PinDriver::output(dc_pin)?;
-
-    let spi_driver = SpiDriver::new(peripherals.spi2, sclk, mosi, miso, &SpiDriverConfig::new())?;
-    let spi_cfg = SpiConfig::new()
-        .baudrate(SPI_BAUDRATE_HZ.Hz())
-        .write_only(true)
-        .data_mode(MODE_0);
-    let spi_device = SpiDeviceDriver::new(spi_driver, Some(cs_pin), &spi_cfg)?;
-    let di = SPIInterfaceNoCS::new(spi_device, dc);
-    let mut delay = FreeRtos;
-    let mut display = ST7789::new(di, Some(rst), Some(backlight), LCD_WIDTH, LCD_HEIGHT);
-    display.init(&mut delay).map_err(map_st7789_error)?;
-    display
-        .set_orientation(Orientation::PortraitSwapped)
-        .map_err(map_st7789_error)?;
+    let (mut display, _backlight) = board::init_display(peripherals.spi2)?;
+    let size = display.bounding_box().size;
 
     // Static intro
-    display.clear(Rgb565::BLACK).map_err(map_st7789_error)?;
     let style1 = MonoTextStyle::new(&FONT_10X20, Rgb565::CSS_LIME);
     let style2 = MonoTextStyle::new(&FONT_10X20, Rgb565::CSS_YELLOW);
     Text::new("Hello Rust + ESP32!", Point::new(20, 30), style1)
@@ -102,8 +77,8 @@ pub fn run() -> Result<()> {
         (5, 7),
         (6, 7),
     ];
-    let cx = (LCD_WIDTH / 2) as f32;
-    let cy = (LCD_HEIGHT / 2) as f32;
+    let cx = (size.width / 2) as f32;
+    let cy = (size.height / 2) as f32;
     let scale = 60.0;
     let dist = 3.0;
     let angle_step = 0.05;
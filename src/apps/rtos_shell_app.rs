@@ -4,58 +4,85 @@
 //! Interactive RTOS shell example on ESP32 using FreeRTOS.
 //!  
 
-use anyhow::{anyhow, Result};
-use display_interface_spi::SPIInterfaceNoCS;
+use anyhow::{anyhow, bail, Result};
 use embedded_graphics::{
     mono_font::{ascii::FONT_6X10, MonoTextStyle},
     pixelcolor::Rgb565,
     prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
     text::Text,
 };
-use esp_idf_hal::{
-    delay::FreeRtos,
-    gpio::{AnyIOPin, Output, PinDriver},
-    peripherals::Peripherals,
-    prelude::*,
-    spi::{Dma, SpiConfig, SpiDeviceDriver, SpiDriver, SpiDriverConfig},
+use esp_idf_hal::{delay::FreeRtos, modem::Modem, peripherals::Peripherals};
+use esp_idf_svc::{
+    eventloop::EspSystemEventLoop,
+    nvs::{EspDefaultNvsPartition, EspNvs},
 };
-use esp_idf_svc::{eventloop::EspSystemEventLoop, log::EspLogger, nvs::EspDefaultNvsPartition};
 use esp_idf_sys as _; // Keeps `binstart` linkage
 use log::*;
-use st7789::{Orientation, ST7789};
 use std::{
     collections::VecDeque,
-    fs,
-    io::{stdin, BufRead},
+    io::{stdin, stdout, ErrorKind, Read, Write},
+    net::TcpListener,
     path::Path,
-    sync::{Arc, Mutex},
+    sync::Arc,
     thread,
+    time::{Duration, Instant},
+};
+
+use super::App;
+use crate::board::{self, map_st7789_error};
+use crate::logger;
+use crate::shell::line_editor::{self, LineEditor};
+use crate::shell::nvs::NvsCommand;
+use crate::shell::sys::LastCrashCommand;
+use crate::shell::{
+    self, script, ArgSpec, ChannelOutput, ConsoleOutput, Context, Output, OutputEvent, Scroll,
+    Shell, ShellCommand, TeeOutput,
 };
+use crate::shell::{telnet, CommandRegistry};
+use crate::storage;
+use crate::wifi;
 
-// Display parameters
-const LCD_WIDTH: u16 = 240;
-const LCD_HEIGHT: u16 = 320;
-const SPI_BAUDRATE_HZ: u32 = 40_000_000;
+// Display parameters (from the board profile)
+const LCD_WIDTH: u16 = match board::BOARD.display {
+    Some(display) => display.width,
+    None => 240,
+};
+const LCD_HEIGHT: u16 = match board::BOARD.display {
+    Some(display) => display.height,
+    None => 320,
+};
 
 // Shell layout
 const MAX_SHELL_LOG_LINES: usize = 18;
+const SCROLLBACK_LINES: usize = 200;
 const SHELL_START_Y: i32 = 15;
 const SHELL_LINE_HEIGHT: i32 = 15;
 
-// SPIFFS mount point (unused stub)
-const FS_MOUNT_POINT: &str = "/spiffs";
+// Warning/error overlay
+const OVERLAY_LINES: usize = 3;
+const OVERLAY_DURATION: Duration = Duration::from_secs(10);
+
+// Serial console
+const PROMPT: &str = "esp> ";
+const HISTORY_FILE: &str = "/spiffs/.shell_history";
 
-// Shared shell state
+// On-screen shell log, owned by the display loop and fed from the shell thread
+// through a channel, so running a command never holds display state.
+// Keeps `SCROLLBACK_LINES` lines, of which `MAX_SHELL_LOG_LINES` are visible.
 #[derive(Debug)]
-struct SharedState {
+struct ShellScreen {
     shell_log: VecDeque<String>,
+    /// Lines scrolled back from the newest one; 0 follows new output.
+    scroll_offset: usize,
     needs_redraw: bool,
 }
 
-impl SharedState {
+impl ShellScreen {
     fn new() -> Self {
         Self {
-            shell_log: VecDeque::with_capacity(MAX_SHELL_LOG_LINES),
+            shell_log: VecDeque::with_capacity(SCROLLBACK_LINES),
+            scroll_offset: 0,
             needs_redraw: true,
         }
     }
@@ -70,195 +97,377 @@ impl SharedState {
                 line.push(' ');
                 line.push_str(word);
             } else {
-                if self.shell_log.len() >= MAX_SHELL_LOG_LINES {
-                    self.shell_log.pop_front();
-                }
-                self.shell_log.push_back(line.clone());
+                self.push_line(line.clone());
                 line.clear();
                 line.push_str(word);
             }
         }
         if !line.is_empty() {
-            if self.shell_log.len() >= MAX_SHELL_LOG_LINES {
-                self.shell_log.pop_front();
+            self.push_line(line);
+        }
+        // New output jumps back to the bottom, like a terminal.
+        self.scroll_offset = 0;
+        self.needs_redraw = true;
+    }
+
+    fn push_line(&mut self, line: String) {
+        if self.shell_log.len() >= SCROLLBACK_LINES {
+            self.shell_log.pop_front();
+        }
+        self.shell_log.push_back(line);
+    }
+
+    fn apply(&mut self, event: OutputEvent) {
+        match event {
+            OutputEvent::Line(line) => self.add_shell_message(line),
+            OutputEvent::Clear => {
+                self.shell_log.clear();
+                self.scroll_offset = 0;
+                self.needs_redraw = true;
             }
-            self.shell_log.push_back(line);
+            OutputEvent::Scroll(scroll) => self.scroll(scroll),
         }
+    }
+
+    fn scroll(&mut self, scroll: Scroll) {
+        let max = self.shell_log.len().saturating_sub(MAX_SHELL_LOG_LINES);
+        self.scroll_offset = match scroll {
+            Scroll::Up(lines) => (self.scroll_offset + lines).min(max),
+            Scroll::Down(lines) => self.scroll_offset.saturating_sub(lines),
+            Scroll::Top => max,
+            Scroll::Bottom => 0,
+        };
         self.needs_redraw = true;
     }
+
+    /// Lines currently in the visible window.
+    fn visible(&self) -> impl Iterator<Item = &String> {
+        let end = self.shell_log.len() - self.scroll_offset;
+        let start = end.saturating_sub(MAX_SHELL_LOG_LINES);
+        self.shell_log.range(start..end)
+    }
+}
+
+/// Recent warnings and errors from the logger, shown on top of the shell log
+/// for [`OVERLAY_DURATION`] after the last one arrived.
+struct LogOverlay {
+    /// Sequence number of the last log record looked at.
+    seen: u64,
+    lines: VecDeque<String>,
+    shown_since: Option<Instant>,
+}
+
+impl LogOverlay {
+    fn new() -> Self {
+        Self {
+            seen: logger::logger().last_seq(),
+            lines: VecDeque::with_capacity(OVERLAY_LINES),
+            shown_since: None,
+        }
+    }
+
+    /// Picks up new warnings/errors and expires old ones. Returns true if the
+    /// overlay changed and the screen needs a redraw.
+    fn update(&mut self) -> bool {
+        let logger = logger::logger();
+        let last = logger.last_seq();
+        if last != self.seen {
+            let entries = logger.entries_since(self.seen, Level::Warn);
+            self.seen = last;
+            if !entries.is_empty() {
+                let max_chars = (LCD_WIDTH / 6) as usize - 2;
+                for entry in entries {
+                    let target = entry.target.rsplit("::").next().unwrap_or_default();
+                    let line = format!("{} {}: {}", entry.level, target, entry.message)
+                        .chars()
+                        .take(max_chars)
+                        .collect();
+                    if self.lines.len() >= OVERLAY_LINES {
+                        self.lines.pop_front();
+                    }
+                    self.lines.push_back(line);
+                }
+                self.shown_since = Some(Instant::now());
+                return true;
+            }
+        }
+        if self
+            .shown_since
+            .is_some_and(|since| since.elapsed() >= OVERLAY_DURATION)
+        {
+            self.lines.clear();
+            self.shown_since = None;
+            return true;
+        }
+        false
+    }
+}
+
+/// `scroll`: pages the on-screen scrollback. Registered by this app only.
+///
+/// The request goes through the display's output channel, so it is applied after
+/// the `> scroll` echo rather than being undone by it.
+struct ScrollCommand {
+    lcd: ChannelOutput,
 }
 
-// Map ST7789 errors
-fn map_st7789_error<E: core::fmt::Debug>(err: st7789::Error<E>) -> anyhow::Error {
-    anyhow!("ST7789 error: {:?}", err)
+impl ShellCommand for ScrollCommand {
+    fn name(&self) -> &str {
+        "scroll"
+    }
+
+    fn help(&self) -> &str {
+        "Page the on-screen log (default: one page)"
+    }
+
+    fn args(&self) -> ArgSpec {
+        ArgSpec::new("<up|down|top|bottom> [lines]", 1, Some(2))
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[&str], _out: &mut dyn Output) -> Result<()> {
+        let lines = match args.get(1) {
+            Some(n) => n
+                .parse()
+                .map_err(|_| anyhow!("invalid line count: {}", n))?,
+            None => MAX_SHELL_LOG_LINES - 1,
+        };
+        let scroll = match args[0] {
+            "up" => Scroll::Up(lines),
+            "down" => Scroll::Down(lines),
+            "top" => Scroll::Top,
+            "bottom" => Scroll::Bottom,
+            other => bail!("unknown direction: {}", other),
+        };
+        self.lcd.send(OutputEvent::Scroll(scroll));
+        Ok(())
+    }
 }
 
-// Stub filesystem init (no-op)
-fn init_fs() -> Result<&'static Path> {
-    info!("Skipping SPIFFS init (stub)");
-    Ok(Path::new(FS_MOUNT_POINT))
+/// Writes terminal output from the line editor to the serial console.
+fn print_term(text: &str) {
+    let mut out = stdout();
+    let _ = out.write_all(text.as_bytes());
+    let _ = out.flush();
+}
+
+/// Remote shell configuration read from NVS.
+struct TelnetSettings {
+    password: String,
+    port: u16,
+}
+
+/// `None` while no password is stored: the remote shell is never started without one.
+fn telnet_settings(partition: &EspDefaultNvsPartition) -> Result<Option<TelnetSettings>> {
+    let nvs = EspNvs::new(partition.clone(), telnet::NVS_NAMESPACE, true)?;
+    let Some(password) = wifi::read_str(&nvs, telnet::NVS_PASSWORD_KEY)? else {
+        return Ok(None);
+    };
+    let port = nvs
+        .get_u16(telnet::NVS_PORT_KEY)?
+        .unwrap_or(telnet::DEFAULT_PORT);
+    Ok(Some(TelnetSettings { password, port }))
+}
+
+/// Joins Wi-Fi and serves telnet sessions on the shared registry in background
+/// tasks, rejoining whenever the station drops, if credentials and a telnet
+/// password are stored in NVS.
+fn start_remote_shell(
+    modem: Modem,
+    sysloop: EspSystemEventLoop,
+    nvs: EspDefaultNvsPartition,
+    registry: Arc<CommandRegistry>,
+) -> Result<()> {
+    let Some(credentials) = wifi::load_credentials(&nvs)? else {
+        info!("No Wi-Fi credentials in NVS, remote shell disabled");
+        return Ok(());
+    };
+    let Some(settings) = telnet_settings(&nvs)? else {
+        warn!(
+            "No password in NVS ({}/{}), remote shell disabled",
+            telnet::NVS_NAMESPACE,
+            telnet::NVS_PASSWORD_KEY
+        );
+        return Ok(());
+    };
+
+    thread::Builder::new().stack_size(8192).spawn(move || {
+        let result = wifi::connect(modem, sysloop, nvs, &credentials).and_then(|mut wifi| {
+            let listener = TcpListener::bind(("0.0.0.0", settings.port))?;
+            info!("Remote shell listening on port {}", settings.port);
+            thread::Builder::new().stack_size(8192).spawn(move || {
+                if let Err(e) = telnet::serve(listener, registry, Some(settings.password)) {
+                    error!("Remote shell failed: {:?}", e);
+                }
+            })?;
+            // The listener is bound to every address, so it serves again once the
+            // station is back.
+            wifi::stay_connected(&mut wifi);
+            Ok(())
+        });
+        if let Err(e) = result {
+            error!("Remote shell failed: {:?}", e);
+        }
+    })?;
+    Ok(())
+}
+
+/// Registry entry for [`run`].
+pub struct RtosShellApp;
+
+impl App for RtosShellApp {
+    fn name(&self) -> &'static str {
+        "rtos_shell_app"
+    }
+
+    fn description(&self) -> &'static str {
+        "Serial shell mirrored on the display"
+    }
+
+    fn run(&self) -> Result<()> {
+        run()
+    }
 }
 
 /// Runs the RTOS shell:  
 /// starts FreeRTOS tasks for command handling and REPL.
 pub fn run() -> Result<()> {
-    esp_idf_sys::link_patches();
-    EspLogger::initialize_default();
-    let _evtloop = EspSystemEventLoop::take()?;
-    let _nvs = EspDefaultNvsPartition::take()?;
+    let sysloop = EspSystemEventLoop::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
 
     info!("Starting RTOS Shell App");
-    let _ = init_fs().unwrap_or_else(|e| {
-        error!("SPIFFS init failed: {}", e);
-        Path::new("/")
-    });
+    let fs_mounted = match storage::mount() {
+        Ok(_) => true,
+        Err(e) => {
+            error!("SPIFFS init failed: {}", e);
+            false
+        }
+    };
 
     let peripherals = Peripherals::take()?;
 
-    // SPI & control pins
-    let sclk = peripherals.pins.gpio14;
-    let mosi = peripherals.pins.gpio13;
-    let cs = peripherals.pins.gpio15;
-    let dc = peripherals.pins.gpio2;
-    let rst = peripherals.pins.gpio4;
-    let bl = peripherals.pins.gpio21;
-
-    let mut backlight = PinDriver::output(bl)?;
-    let rst_drv = PinDriver::output(rst)?;
-    let dc_drv = PinDriver::output(dc)?;
-
-    // SPI setup
-    let spi_drv = SpiDriver::new(
-        peripherals.spi2,
-        sclk,
-        mosi,
-        None::<AnyIOPin>,
-        &SpiDriverConfig::new().dma(Dma::Disabled),
-    )?;
-    let spi_dev = SpiDeviceDriver::new(
-        spi_drv,
-        Some(cs),
-        &SpiConfig::new()
-            .baudrate(SPI_BAUDRATE_HZ.Hz())
-            .write_only(true),
-    )?;
-    let iface = SPIInterfaceNoCS::new(spi_dev, dc_drv);
-
-    // Display init
-    let mut delay = FreeRtos;
-    let mut disp: ST7789<_, _, PinDriver<'static, AnyIOPin, Output>> =
-        ST7789::new(iface, Some(rst_drv), None, LCD_WIDTH, LCD_HEIGHT);
-    disp.init(&mut delay).map_err(map_st7789_error)?;
-    disp.set_orientation(Orientation::PortraitSwapped)
-        .map_err(map_st7789_error)?;
-    disp.clear(Rgb565::BLACK).map_err(map_st7789_error)?;
-    backlight.set_high()?;
-
-    // Shared state & display handle
-    let shared = Arc::new(Mutex::new(SharedState::new()));
-    let display = Arc::new(Mutex::new(disp));
+    // Display init (pins and SPI setup come from the board profile)
+    let (mut display, _backlight) = board::init_display(peripherals.spi2)?;
+
+    // Shell output flows to the display loop over a channel
+    let (mut lcd, events) = ChannelOutput::channel();
+    let mut screen = ShellScreen::new();
+    let mut overlay = LogOverlay::new();
+
+    let mut registry = shell::default_registry();
+    registry.register(ScrollCommand { lcd: lcd.clone() });
+    registry.register(NvsCommand::new(nvs.clone()));
+    registry.register(LastCrashCommand::new(nvs.clone()));
+    let registry = Arc::new(registry);
+
+    // Remote sessions share the registry; a failure here leaves the serial shell usable.
+    if let Err(e) = start_remote_shell(peripherals.modem, sysloop, nvs, registry.clone()) {
+        error!("Remote shell not started: {:?}", e);
+    }
 
     // Shell task
     {
-        let shared = Arc::clone(&shared);
-        thread::Builder::new().stack_size(4096).spawn(move || {
-            let stdin = stdin();
-            let mut reader = stdin.lock();
-            let mut buf = String::new();
-
-            shared
-                .lock()
-                .unwrap()
-                .add_shell_message("Type 'help' for commands.".into());
-            while reader.read_line(&mut buf).ok().filter(|&n| n > 0).is_some() {
-                let input = buf.trim().to_string();
-                buf.clear();
-                if input.is_empty() {
-                    continue;
-                }
+        let mut shell = Shell::new(registry);
+        thread::Builder::new().stack_size(8192).spawn(move || {
+            let mut stdin = stdin();
+            let mut byte = [0u8; 1];
+            let mut term = String::new();
+            let mut editor = LineEditor::new(PROMPT);
+            if fs_mounted {
+                editor.set_history(line_editor::load_history(HISTORY_FILE));
+            }
+
+            if fs_mounted && Path::new(script::AUTORUN_FILE).exists() {
+                info!("Running {}", script::AUTORUN_FILE);
+                let mut output = TeeOutput::new(ConsoleOutput, lcd.clone());
+                let status = shell.run_script(script::AUTORUN_FILE, &mut output);
+                info!("{} finished with status {}", script::AUTORUN_FILE, status);
+            }
 
-                let mut st = shared.lock().unwrap();
-                st.add_shell_message(format!("> {}", input));
-                match input.split_whitespace().next() {
-                    Some("help") => st.add_shell_message("help, info, clear, ls [path]".into()),
-                    Some("info") => {
-                        let idf = unsafe {
-                            let vp = esp_idf_sys::esp_get_idf_version();
-                            if vp.is_null() {
-                                "unknown"
-                            } else {
-                                std::ffi::CStr::from_ptr(vp).to_str().unwrap_or("<??>")
-                            }
-                        };
-                        st.add_shell_message(format!("IDF: {}", idf));
-                        let free = unsafe { esp_idf_sys::esp_get_free_heap_size() };
-                        st.add_shell_message(format!("Heap: {} bytes", free));
+            lcd.write_line("Type 'help' for commands.");
+            print_term(editor.prompt());
+            loop {
+                // The console may be non-blocking; poll until a byte arrives.
+                match stdin.read(&mut byte) {
+                    Ok(1) => {}
+                    Ok(_) => {
+                        thread::sleep(Duration::from_millis(10));
+                        continue;
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(10));
+                        continue;
                     }
-                    Some("clear") => {
-                        let mut s = shared.lock().unwrap();
-                        s.shell_log.clear();
-                        s.add_shell_message("Cleared".into());
+                    Err(e) => {
+                        error!("Console read failed: {}", e);
+                        break;
                     }
-                    Some("ls") => {
-                        let path = input
-                            .split_whitespace()
-                            .nth(1)
-                            .map(|p| format!("{}/{}", FS_MOUNT_POINT, p))
-                            .unwrap_or_else(|| FS_MOUNT_POINT.into());
-                        let mut s = shared.lock().unwrap();
-                        s.add_shell_message(format!("Listing {}", path));
-                        match fs::read_dir(&path) {
-                            Ok(entries) => {
-                                let mut any = false;
-                                for e in entries.flatten() {
-                                    let nm = e.file_name().into_string().unwrap_or("?".into());
-                                    let meta = e.metadata().ok();
-                                    let sz = meta
-                                        .map(|m| {
-                                            if m.is_dir() {
-                                                "[DIR]".into()
-                                            } else {
-                                                format!("{}B", m.len())
-                                            }
-                                        })
-                                        .unwrap_or_else(|| "?".into());
-                                    s.add_shell_message(format!("- {} ({})", nm, sz));
-                                    any = true;
-                                }
-                                if !any {
-                                    s.add_shell_message("(empty)".into());
-                                }
-                            }
-                            Err(e) => shared
-                                .lock()
-                                .unwrap()
-                                .add_shell_message(format!("ls error: {}", e)),
+                }
+
+                term.clear();
+                let line = editor.feed(byte[0], &shell, &mut term);
+                print_term(&term);
+                let Some(line) = line else {
+                    continue;
+                };
+
+                let input = line.trim();
+                if !input.is_empty() {
+                    // The terminal already shows the typed line; only the LCD needs it.
+                    lcd.write_line(&format!("> {}", input));
+                    let mut output = TeeOutput::new(ConsoleOutput, lcd.clone());
+                    shell.execute(input, &mut output);
+
+                    if fs_mounted {
+                        if let Err(e) = line_editor::save_history(HISTORY_FILE, editor.history()) {
+                            warn!("Could not save shell history: {}", e);
                         }
                     }
-                    Some(cmd) => st.add_shell_message(format!("Unknown: {}", cmd)),
-                    None => {}
                 }
+                print_term(editor.prompt());
             }
         })?;
     }
 
     // Display refresh loop
     loop {
-        let mut s = shared.lock().unwrap();
-        let redraw = s.needs_redraw;
-        s.needs_redraw = false;
-        drop(s);
-
-        if redraw {
-            let s = shared.lock().unwrap();
-            let mut d = display.lock().unwrap();
-            d.clear(Rgb565::BLACK).map_err(map_st7789_error)?;
+        while let Ok(event) = events.try_recv() {
+            screen.apply(event);
+        }
+        if overlay.update() {
+            screen.needs_redraw = true;
+        }
+
+        if screen.needs_redraw {
+            screen.needs_redraw = false;
+            display.clear(Rgb565::BLACK).map_err(map_st7789_error)?;
             let style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_LIGHT_BLUE);
             let mut y = SHELL_START_Y;
-            for line in &s.shell_log {
+            for line in screen.visible() {
                 if y < (LCD_HEIGHT as i32 - SHELL_LINE_HEIGHT) {
                     Text::new(line, Point::new(5, y), style)
-                        .draw(&mut *d)
+                        .draw(&mut display)
+                        .map_err(map_st7789_error)?;
+                    y += SHELL_LINE_HEIGHT;
+                }
+            }
+            if screen.scroll_offset > 0 {
+                let marker = format!("-- {} more lines below --", screen.scroll_offset);
+                let marker_style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_YELLOW);
+                Text::new(&marker, Point::new(5, LCD_HEIGHT as i32 - 5), marker_style)
+                    .draw(&mut display)
+                    .map_err(map_st7789_error)?;
+            }
+            if !overlay.lines.is_empty() {
+                let height = overlay.lines.len() as i32 * SHELL_LINE_HEIGHT + 5;
+                Rectangle::new(Point::zero(), Size::new(LCD_WIDTH as u32, height as u32))
+                    .into_styled(PrimitiveStyle::with_fill(Rgb565::CSS_DARK_RED))
+                    .draw(&mut display)
+                    .map_err(map_st7789_error)?;
+                let overlay_style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+                let mut y = SHELL_START_Y - 3;
+                for line in &overlay.lines {
+                    Text::new(line, Point::new(5, y), overlay_style)
+                        .draw(&mut display)
                         .map_err(map_st7789_error)?;
                     y += SHELL_LINE_HEIGHT;
                 }
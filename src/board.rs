@@ -0,0 +1,200 @@
+//! # board
+//! Board support: pin assignments and display bring-up shared by all apps.
+//!
+//! Pins are described as plain GPIO numbers in a [`BoardProfile`], so supporting
+//! another board means adding a profile to `boards.toml` rather than copying
+//! SPI/ST7789 setup code. The profile is chosen at build time with a `board-*`
+//! cargo feature or the `BOARD` env var (see build.rs).
+//!
+
+/// Display orientation, mirrored from `st7789::Orientation` so profiles
+/// can be described without the graphics feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayOrientation {
+    Portrait,
+    Landscape,
+    PortraitSwapped,
+    LandscapeSwapped,
+}
+
+/// Wiring and geometry of an SPI-attached ST7789 panel.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayProfile {
+    pub sclk: i32,
+    pub mosi: i32,
+    pub miso: Option<i32>,
+    pub cs: i32,
+    pub dc: i32,
+    pub rst: Option<i32>,
+    pub backlight: i32,
+    pub width: u16,
+    pub height: u16,
+    pub orientation: DisplayOrientation,
+    pub spi_baudrate_hz: u32,
+    /// DMA buffer size in bytes, `None` to disable DMA.
+    pub dma_buffer_size: Option<usize>,
+}
+
+/// Common-anode/cathode RGB LED wired to three GPIOs.
+#[derive(Debug, Clone, Copy)]
+pub struct RgbLedProfile {
+    pub red: i32,
+    pub green: i32,
+    pub blue: i32,
+}
+
+/// Everything the apps need to know about the board they run on.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardProfile {
+    /// Key of the profile in `boards.toml`.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub status_led: Option<i32>,
+    pub rgb_led: Option<RgbLedProfile>,
+    pub display: Option<DisplayProfile>,
+}
+
+// Defines `BOARD`, generated by build.rs from the profile selected in `boards.toml`.
+include!(concat!(env!("OUT_DIR"), "/board_profile.rs"));
+
+/// GPIOs no board may hand out: UART0 carries the serial console, 6-11 the SPI flash.
+const SYSTEM_PINS: [(i32, &str); 8] = [
+    (1, "console TX"),
+    (3, "console RX"),
+    (6, "SPI flash"),
+    (7, "SPI flash"),
+    (8, "SPI flash"),
+    (9, "SPI flash"),
+    (10, "SPI flash"),
+    (11, "SPI flash"),
+];
+
+/// Pins that must not be reconfigured at runtime, with what uses them: the
+/// console and flash pins plus the display/SPI wiring of [`BOARD`].
+pub fn reserved_pins() -> Vec<(i32, &'static str)> {
+    let mut pins = SYSTEM_PINS.to_vec();
+    if let Some(display) = BOARD.display {
+        pins.extend([
+            (display.sclk, "display SCLK"),
+            (display.mosi, "display MOSI"),
+            (display.cs, "display CS"),
+            (display.dc, "display DC"),
+            (display.backlight, "display backlight"),
+        ]);
+        pins.extend(display.miso.map(|pin| (pin, "display MISO")));
+        pins.extend(display.rst.map(|pin| (pin, "display RST")));
+    }
+    pins.sort();
+    pins
+}
+
+/// What `pin` is reserved for, if anything (see [`reserved_pins`]).
+pub fn reserved_pin(pin: i32) -> Option<&'static str> {
+    reserved_pins()
+        .into_iter()
+        .find(|(reserved, _)| *reserved == pin)
+        .map(|(_, owner)| owner)
+}
+
+#[cfg(feature = "graphics-support")]
+pub use display::*;
+
+#[cfg(feature = "graphics-support")]
+mod display {
+    use anyhow::{anyhow, Result};
+    use display_interface_spi::SPIInterfaceNoCS;
+    use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+    use embedded_hal::spi::MODE_0;
+    use esp_idf_hal::{
+        delay::FreeRtos,
+        gpio::{AnyIOPin, AnyOutputPin, Output, PinDriver},
+        prelude::*,
+        spi::{Dma, SpiConfig, SpiDeviceDriver, SpiDriver, SpiDriverConfig, SPI2},
+    };
+    use st7789::{Orientation, ST7789};
+
+    use super::{DisplayOrientation, BOARD};
+
+    /// Push-pull output used for the display control lines and backlight.
+    pub type OutputPin = PinDriver<'static, AnyOutputPin, Output>;
+
+    /// SPI link to the panel (chip select handled by the SPI device driver).
+    pub type DisplayInterface =
+        SPIInterfaceNoCS<SpiDeviceDriver<'static, SpiDriver<'static>>, OutputPin>;
+
+    /// Ready-to-draw ST7789 handle returned by [`init_display`].
+    pub type Display = ST7789<DisplayInterface, OutputPin, OutputPin>;
+
+    pub fn map_st7789_error<E: core::fmt::Debug>(err: st7789::Error<E>) -> anyhow::Error {
+        anyhow!("ST7789 driver error: {:?}", err)
+    }
+
+    impl From<DisplayOrientation> for Orientation {
+        fn from(orientation: DisplayOrientation) -> Self {
+            match orientation {
+                DisplayOrientation::Portrait => Orientation::Portrait,
+                DisplayOrientation::Landscape => Orientation::Landscape,
+                DisplayOrientation::PortraitSwapped => Orientation::PortraitSwapped,
+                DisplayOrientation::LandscapeSwapped => Orientation::LandscapeSwapped,
+            }
+        }
+    }
+
+    /// Brings up SPI2 and the ST7789 described by [`BOARD`].
+    ///
+    /// Returns the initialized display (cleared to black, orientation applied)
+    /// and the backlight pin, already switched on.
+    pub fn init_display(spi: SPI2) -> Result<(Display, OutputPin)> {
+        let profile = BOARD
+            .display
+            .ok_or_else(|| anyhow!("board {} has no display", BOARD.name))?;
+        log::info!(
+            "Initializing {}x{} display on {}",
+            profile.width,
+            profile.height,
+            BOARD.name
+        );
+
+        // SAFETY: the profile is the single owner of these GPIOs; apps using the
+        // display must not take the same pins from `Peripherals`.
+        let (sclk, mosi, miso, cs, dc, rst, bl) = unsafe {
+            (
+                AnyOutputPin::new(profile.sclk),
+                AnyOutputPin::new(profile.mosi),
+                profile.miso.map(|pin| AnyIOPin::new(pin)),
+                AnyOutputPin::new(profile.cs),
+                AnyOutputPin::new(profile.dc),
+                profile.rst.map(|pin| AnyOutputPin::new(pin)),
+                AnyOutputPin::new(profile.backlight),
+            )
+        };
+
+        let mut backlight = PinDriver::output(bl)?;
+        backlight.set_low()?;
+        let dc = PinDriver::output(dc)?;
+        let rst = rst.map(PinDriver::output).transpose()?;
+
+        let dma = match profile.dma_buffer_size {
+            Some(size) => Dma::Auto(size),
+            None => Dma::Disabled,
+        };
+        let spi_driver = SpiDriver::new(spi, sclk, mosi, miso, &SpiDriverConfig::new().dma(dma))?;
+        let spi_cfg = SpiConfig::new()
+            .baudrate(profile.spi_baudrate_hz.Hz())
+            .write_only(true)
+            .data_mode(MODE_0);
+        let spi_device = SpiDeviceDriver::new(spi_driver, Some(cs), &spi_cfg)?;
+        let di = SPIInterfaceNoCS::new(spi_device, dc);
+
+        let mut display: Display = ST7789::new(di, rst, None, profile.width, profile.height);
+        display.init(&mut FreeRtos).map_err(map_st7789_error)?;
+        display
+            .set_orientation(profile.orientation.into())
+            .map_err(map_st7789_error)?;
+        display.clear(Rgb565::BLACK).map_err(map_st7789_error)?;
+
+        backlight.set_high()?;
+        log::info!("Display ready");
+        Ok((display, backlight))
+    }
+}
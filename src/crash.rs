@@ -0,0 +1,317 @@
+//! # crash
+//! Crash reporter: a panic hook records the panic in RTC memory, and the next boot
+//! turns it (or a watchdog/panic reset without one) into a report kept in NVS.
+//!
+//! RTC slow memory survives the reset that follows a panic, so the hook doesn't have
+//! to touch flash; the report is moved to NVS at boot so it also survives power
+//! cycles. The shell shows it with `lastcrash`.
+//!
+
+use std::fmt::{self, Write};
+use std::panic::{self, Location};
+use std::ptr::addr_of_mut;
+use std::thread;
+
+use anyhow::Result;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs};
+use log::{error, warn};
+
+use crate::system;
+
+/// NVS location of the last report.
+pub const NVS_NAMESPACE: &str = "crash";
+const NVS_REPORT_KEY: &str = "last";
+
+/// Marks a valid [`RtcCrash`]; anything else is power-on garbage.
+const RTC_MAGIC: u32 = 0xC7A5_4ED1;
+const MAX_MESSAGE: usize = 192;
+const MAX_LOCATION: usize = 96;
+const MAX_THREAD: usize = 16;
+const MAX_FRAMES: usize = 16;
+
+/// Panic record as laid out in RTC memory. Fixed-size so the hook needn't allocate.
+#[repr(C)]
+struct RtcCrash {
+    magic: u32,
+    message_len: u32,
+    message: [u8; MAX_MESSAGE],
+    location_len: u32,
+    location: [u8; MAX_LOCATION],
+    thread_len: u32,
+    thread: [u8; MAX_THREAD],
+    frame_count: u32,
+    frames: [u32; MAX_FRAMES],
+    uptime_ms: u64,
+}
+
+// Not initialized at boot (NOLOAD), so it keeps its contents across resets.
+#[link_section = ".rtc_noinit"]
+static mut RTC_CRASH: RtcCrash = RtcCrash {
+    magic: 0,
+    message_len: 0,
+    message: [0; MAX_MESSAGE],
+    location_len: 0,
+    location: [0; MAX_LOCATION],
+    thread_len: 0,
+    thread: [0; MAX_THREAD],
+    frame_count: 0,
+    frames: [0; MAX_FRAMES],
+    uptime_ms: 0,
+};
+
+/// Copies as much of `text` as fits into `buf`, returning the length used.
+fn store_str(buf: &mut [u8], text: &str) -> u32 {
+    let mut len = text.len().min(buf.len());
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    buf[..len].copy_from_slice(&text.as_bytes()[..len]);
+    len as u32
+}
+
+/// Writes formatted text into a fixed buffer, dropping whatever doesn't fit.
+struct FixedWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for FixedWriter<'_> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.len += store_str(&mut self.buf[self.len..], text) as usize;
+        Ok(())
+    }
+}
+
+fn load_str(buf: &[u8], len: u32) -> String {
+    let len = (len as usize).min(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Return addresses of the calling stack, ready for `xtensa-esp32-elf-addr2line`.
+#[cfg(target_arch = "xtensa")]
+fn backtrace(frames: &mut [u32]) -> usize {
+    use esp_idf_sys::{
+        esp_backtrace_frame_t, esp_backtrace_get_next_frame, esp_backtrace_get_start,
+    };
+
+    let mut frame = esp_backtrace_frame_t::default();
+    unsafe { esp_backtrace_get_start(&mut frame.pc, &mut frame.sp, &mut frame.next_pc) };
+    let mut count = 0;
+    while count < frames.len() {
+        // Return addresses carry the call window size in the top bits.
+        let pc = (frame.pc & 0x3fff_ffff) | 0x4000_0000;
+        frames[count] = pc.saturating_sub(3);
+        count += 1;
+        if frame.next_pc == 0 || !unsafe { esp_backtrace_get_next_frame(&mut frame) } {
+            break;
+        }
+    }
+    count
+}
+
+#[cfg(not(target_arch = "xtensa"))]
+fn backtrace(_frames: &mut [u32]) -> usize {
+    0
+}
+
+fn record_panic(message: &str, location: Option<&Location>) {
+    let current = thread::current();
+
+    // SAFETY: only the panicking thread writes here, and the chip resets right after.
+    let crash = unsafe { &mut *addr_of_mut!(RTC_CRASH) };
+    crash.message_len = store_str(&mut crash.message, message);
+    let mut writer = FixedWriter {
+        buf: &mut crash.location,
+        len: 0,
+    };
+    if let Some(location) = location {
+        let _ = write!(writer, "{}:{}", location.file(), location.line());
+    }
+    crash.location_len = writer.len as u32;
+    crash.thread_len = store_str(&mut crash.thread, current.name().unwrap_or("?"));
+    crash.frame_count = backtrace(&mut crash.frames) as u32;
+    crash.uptime_ms = system::uptime().as_millis() as u64;
+    crash.magic = RTC_MAGIC;
+}
+
+/// Installs the panic hook. The default hook still runs afterwards, so panics are
+/// printed on the console as before.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("(non-string panic payload)");
+        record_panic(message, info.location());
+        default_hook(info);
+    }));
+}
+
+/// What is known about the last crash.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub reset_reason: &'static str,
+    /// `None` if the reset wasn't caused by a Rust panic (e.g. a watchdog).
+    pub message: Option<String>,
+    pub location: String,
+    pub thread: String,
+    pub uptime_ms: u64,
+    pub backtrace: Vec<u32>,
+}
+
+impl CrashReport {
+    /// Human-readable report, as stored in NVS and shown by `lastcrash`.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Reset reason: {}", self.reset_reason)];
+        match &self.message {
+            Some(message) => {
+                lines.push(format!("Panic: {}", message));
+                lines.push(format!("At: {}", self.location));
+                lines.push(format!(
+                    "Thread: {}, uptime {}.{:03}s",
+                    self.thread,
+                    self.uptime_ms / 1000,
+                    self.uptime_ms % 1000
+                ));
+            }
+            None => lines.push("No panic recorded (not a Rust panic)".to_string()),
+        }
+        if !self.backtrace.is_empty() {
+            let frames: Vec<String> = self
+                .backtrace
+                .iter()
+                .map(|pc| format!("0x{:08x}", pc))
+                .collect();
+            lines.push(format!("Backtrace: {}", frames.join(" ")));
+        }
+        lines
+    }
+}
+
+/// Collects the crash from before this boot, if there was one, clears the RTC
+/// record and saves the report to NVS. Call once, early in `main`.
+pub fn take_previous() -> Option<CrashReport> {
+    let reset_reason = system::reset_reason();
+    // SAFETY: runs before the panic hook is installed and any other thread starts.
+    let crash = unsafe { &mut *addr_of_mut!(RTC_CRASH) };
+    let recorded = crash.magic == RTC_MAGIC;
+    crash.magic = 0;
+
+    if !recorded && !system::reset_was_crash() {
+        return None;
+    }
+    let report = if recorded {
+        let frame_count = (crash.frame_count as usize).min(MAX_FRAMES);
+        CrashReport {
+            reset_reason,
+            message: Some(load_str(&crash.message, crash.message_len)),
+            location: load_str(&crash.location, crash.location_len),
+            thread: load_str(&crash.thread, crash.thread_len),
+            uptime_ms: crash.uptime_ms,
+            backtrace: crash.frames[..frame_count].to_vec(),
+        }
+    } else {
+        CrashReport {
+            reset_reason,
+            message: None,
+            location: String::new(),
+            thread: String::new(),
+            uptime_ms: 0,
+            backtrace: Vec::new(),
+        }
+    };
+
+    for line in report.lines() {
+        error!("Last crash: {}", line);
+    }
+    if let Err(e) = EspDefaultNvsPartition::take()
+        .map_err(anyhow::Error::from)
+        .and_then(|partition| save(&partition, &report))
+    {
+        warn!("Could not save crash report to NVS: {:?}", e);
+    }
+    Some(report)
+}
+
+fn save(partition: &EspDefaultNvsPartition, report: &CrashReport) -> Result<()> {
+    let mut nvs = EspNvs::new(partition.clone(), NVS_NAMESPACE, true)?;
+    nvs.set_str(NVS_REPORT_KEY, &report.lines().join("\n"))?;
+    Ok(())
+}
+
+/// The last saved report, one line per entry.
+pub fn load(partition: &EspDefaultNvsPartition) -> Result<Option<Vec<String>>> {
+    let nvs = EspNvs::new(partition.clone(), NVS_NAMESPACE, true)?;
+    let Some(len) = nvs.str_len(NVS_REPORT_KEY)? else {
+        return Ok(None);
+    };
+    let mut buf = vec![0u8; len];
+    Ok(nvs
+        .get_str(NVS_REPORT_KEY, &mut buf)?
+        .map(|report| report.lines().map(str::to_string).collect()))
+}
+
+/// Forgets the saved report.
+pub fn clear(partition: &EspDefaultNvsPartition) -> Result<()> {
+    let mut nvs = EspNvs::new(partition.clone(), NVS_NAMESPACE, true)?;
+    nvs.remove(NVS_REPORT_KEY)?;
+    Ok(())
+}
+
+/// Shows the report on the display, if the board has one, before the app takes over.
+pub fn show(report: &CrashReport) {
+    #[cfg(feature = "graphics-support")]
+    if crate::board::BOARD.display.is_some() {
+        if let Err(e) = show_on_display(report) {
+            warn!("Could not show crash report: {:?}", e);
+        }
+    }
+    #[cfg(not(feature = "graphics-support"))]
+    let _ = report;
+}
+
+#[cfg(feature = "graphics-support")]
+fn show_on_display(report: &CrashReport) -> Result<()> {
+    use embedded_graphics::{
+        mono_font::{ascii::FONT_6X10, MonoTextStyle},
+        pixelcolor::Rgb565,
+        prelude::*,
+        text::Text,
+    };
+    use esp_idf_hal::spi::SPI2;
+
+    use crate::board::{self, map_st7789_error};
+
+    /// How long the report stays up before booting continues.
+    const SHOW_FOR_MS: u32 = 5000;
+
+    // SAFETY: nothing has taken the peripherals yet, and the display and SPI
+    // driver are dropped again before the app starts.
+    let (mut display, _backlight) = board::init_display(unsafe { SPI2::new() })?;
+    let max_chars = (display.bounding_box().size.width / 6) as usize - 2;
+
+    let title = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_RED);
+    Text::new("Crash on previous run", Point::new(5, 15), title)
+        .draw(&mut display)
+        .map_err(map_st7789_error)?;
+
+    let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+    let mut y = 35;
+    for line in report.lines() {
+        // Wrap by characters: backtraces have no spaces worth breaking at.
+        let chars: Vec<char> = line.chars().collect();
+        for chunk in chars.chunks(max_chars) {
+            let text: String = chunk.iter().collect();
+            Text::new(&text, Point::new(5, y), style)
+                .draw(&mut display)
+                .map_err(map_st7789_error)?;
+            y += 12;
+        }
+    }
+
+    esp_idf_hal::delay::FreeRtos::delay_ms(SHOW_FOR_MS);
+    Ok(())
+}
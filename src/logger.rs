@@ -0,0 +1,219 @@
+//! # logger
+//! Global `log` backend: forwards records to [`EspLogger`] (serial console) and keeps
+//! the latest ones in a RAM ring buffer, optionally also appending them to a
+//! rotating file on flash.
+//!
+//! The ring buffer backs the shell's `dmesg` and the warning overlay of
+//! `rtos_shell_app`, so logs can be read without a serial monitor attached.
+//!
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use anyhow::Result;
+use esp_idf_svc::log::EspLogger;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::system;
+
+/// Records kept in RAM; the oldest are dropped first.
+pub const RING_CAPACITY: usize = 128;
+
+/// Log file on the flash filesystem and its single rotated predecessor.
+pub const LOG_FILE: &str = "/spiffs/log.txt";
+pub const LOG_FILE_OLD: &str = "/spiffs/log.1.txt";
+/// The file is rotated once it grows past this size.
+const LOG_FILE_MAX_BYTES: u64 = 16 * 1024;
+
+/// One captured log record.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// Position in the stream of all records since boot, starting at 1.
+    pub seq: u64,
+    /// Time since boot.
+    pub time: Duration,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// `[  12.345] W target: message`, as shown by `dmesg` and written to the log file.
+    pub fn format(&self) -> String {
+        let level = match self.level {
+            Level::Error => 'E',
+            Level::Warn => 'W',
+            Level::Info => 'I',
+            Level::Debug => 'D',
+            Level::Trace => 'V',
+        };
+        format!(
+            "[{:5}.{:03}] {} {}: {}",
+            self.time.as_secs(),
+            self.time.subsec_millis(),
+            level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+struct LogFile {
+    file: File,
+    len: u64,
+}
+
+impl LogFile {
+    fn open() -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(LOG_FILE)?;
+        let len = file.metadata()?.len();
+        Ok(Self { file, len })
+    }
+
+    fn append(&mut self, line: &str) -> std::io::Result<()> {
+        if self.len + line.len() as u64 > LOG_FILE_MAX_BYTES {
+            let _ = fs::remove_file(LOG_FILE_OLD);
+            fs::rename(LOG_FILE, LOG_FILE_OLD)?;
+            *self = Self::open()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.len += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// The global logger, installed by [`init`].
+pub struct Logger {
+    esp: EspLogger,
+    ring: Mutex<VecDeque<LogEntry>>,
+    file: Mutex<Option<LogFile>>,
+    /// Levels set with [`Logger::set_level`], for display.
+    levels: Mutex<BTreeMap<String, LevelFilter>>,
+    seq: AtomicU64,
+}
+
+static LOGGER: Logger = Logger {
+    esp: EspLogger::new(),
+    ring: Mutex::new(VecDeque::new()),
+    file: Mutex::new(None),
+    levels: Mutex::new(BTreeMap::new()),
+    seq: AtomicU64::new(0),
+};
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Installs the logger. Call once, in place of `EspLogger::initialize_default`.
+pub fn init() {
+    log::set_logger(&LOGGER)
+        .map(|()| LOGGER.esp.initialize())
+        .expect("logger already installed");
+}
+
+pub fn logger() -> &'static Logger {
+    &LOGGER
+}
+
+impl Logger {
+    /// Records in the ring buffer at `min_level` or more severe, oldest first.
+    pub fn entries(&self, min_level: Level) -> Vec<LogEntry> {
+        lock(&self.ring)
+            .iter()
+            .filter(|entry| entry.level <= min_level)
+            .cloned()
+            .collect()
+    }
+
+    /// Records newer than `seq` at `min_level` or more severe, oldest first.
+    pub fn entries_since(&self, seq: u64, min_level: Level) -> Vec<LogEntry> {
+        lock(&self.ring)
+            .iter()
+            .filter(|entry| entry.seq > seq && entry.level <= min_level)
+            .cloned()
+            .collect()
+    }
+
+    /// Sequence number of the latest record, 0 before the first one.
+    pub fn last_seq(&self) -> u64 {
+        self.seq.load(Ordering::SeqCst)
+    }
+
+    pub fn clear(&self) {
+        lock(&self.ring).clear();
+    }
+
+    /// Sets the level of one target (a module path, or `*` for the default).
+    /// Levels above the build's `CONFIG_LOG_MAXIMUM_LEVEL` are still filtered out.
+    pub fn set_level(&self, target: &str, level: LevelFilter) -> Result<()> {
+        self.esp.set_target_level(target, level)?;
+        lock(&self.levels).insert(target.to_string(), level);
+        Ok(())
+    }
+
+    /// Targets whose level was changed with [`Logger::set_level`].
+    pub fn levels(&self) -> BTreeMap<String, LevelFilter> {
+        lock(&self.levels).clone()
+    }
+
+    /// Starts or stops appending records to [`LOG_FILE`]. Needs the filesystem mounted.
+    pub fn set_file_logging(&self, enabled: bool) -> Result<()> {
+        let mut file = lock(&self.file);
+        *file = if enabled {
+            Some(LogFile::open()?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    pub fn file_logging(&self) -> bool {
+        lock(&self.file).is_some()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.esp.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.esp.log(record);
+
+        let entry = LogEntry {
+            seq: self.seq.fetch_add(1, Ordering::SeqCst) + 1,
+            time: system::uptime(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+
+        // Never log while holding these locks: the record would come back here.
+        if let Some(file) = lock(&self.file).as_mut() {
+            // Nowhere to report a failing log file; it just misses the record.
+            let _ = file.append(&entry.format());
+        }
+        let mut ring = lock(&self.ring);
+        if ring.len() >= RING_CAPACITY {
+            ring.pop_front();
+        }
+        ring.push_back(entry);
+    }
+
+    fn flush(&self) {
+        self.esp.flush();
+        if let Some(file) = lock(&self.file).as_mut() {
+            let _ = file.file.flush();
+        }
+    }
+}
@@ -3,25 +3,30 @@
 //!
 
 mod apps;
+mod board;
+mod crash;
+mod logger;
+mod selector;
+mod shell;
+mod storage;
+mod system;
+mod wifi;
 
 /// Application entry point.  
-/// By default runs the `hello_app`.  
+/// Picks an app via [`selector::select_app`] and runs it.  
 fn main() {
-    crate::apps::hello_app::run();
-    // crate::apps::led_blinking_app::run();
+    esp_idf_sys::link_patches();
+    logger::init();
 
-    // #[cfg(feature = "display-support")]
-    // crate::apps::display_backlight_app::run().unwrap();
+    if let Some(report) = crash::take_previous() {
+        crash::show(&report);
+    }
+    crash::install_panic_hook();
 
-    // #[cfg(feature = "graphics-support")]
-    // crate::apps::graphics_app::run().unwrap();
+    let app = selector::select_app();
+    log::info!("Starting {}", app.name());
 
-    // #[cfg(feature = "graphics-support")]
-    // crate::apps::rotating_cube_app::run().unwrap();
-
-    // #[cfg(feature = "graphics-support")]
-    // crate::apps::mud_game_app::run().unwrap();
-
-    // #[cfg(feature = "graphics-support")]
-    // crate::apps::rtos_shell_app::run().unwrap();
+    if let Err(err) = app.run() {
+        panic!("{} failed: {:?}", app.name(), err);
+    }
 }
@@ -0,0 +1,146 @@
+//! # selector
+//! Boot-time app selection.
+//!
+//! The app to run is picked in this order:
+//! 1. a choice typed on the serial console while the boot menu is shown,
+//! 2. the app name stored in NVS (`starter/app`),
+//! 3. the compile-time default (`STARTER_APP` env var, else `hello_app`).
+//!
+//! A choice made on the console is written back to NVS so it sticks across resets.
+
+use std::io::{ErrorKind, Read};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use log::{info, warn};
+
+use crate::apps::{self, App};
+
+/// NVS namespace holding starter-wide settings.
+pub const NVS_NAMESPACE: &str = "starter";
+/// NVS key holding the name of the app to run.
+pub const NVS_APP_KEY: &str = "app";
+
+/// App used when nothing is stored in NVS. Override with `STARTER_APP=<name> cargo build`.
+const DEFAULT_APP: &str = match option_env!("STARTER_APP") {
+    Some(name) => name,
+    None => "hello_app",
+};
+
+/// How long the boot menu waits for the first key press.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Shows the boot menu and returns the app to run.
+pub fn select_app() -> &'static dyn App {
+    let apps = apps::registry();
+
+    let stored = read_stored_app().unwrap_or_else(|e| {
+        warn!("Could not read app selection from NVS: {:?}", e);
+        None
+    });
+    let fallback = stored
+        .as_deref()
+        .and_then(|name| {
+            let app = apps::find(name);
+            if app.is_none() {
+                warn!("NVS selects unknown app '{}', ignoring", name);
+            }
+            app
+        })
+        .or_else(|| apps::find(DEFAULT_APP))
+        .unwrap_or(apps[0]);
+
+    println!();
+    println!("Available apps:");
+    for (i, app) in apps.iter().enumerate() {
+        let marker = if app.name() == fallback.name() {
+            '*'
+        } else {
+            ' '
+        };
+        println!(
+            " {}{}) {:<22} {}",
+            marker,
+            i + 1,
+            app.name(),
+            app.description()
+        );
+    }
+    println!(
+        "Enter a number or name within {}s to switch apps (default: {})",
+        PROMPT_TIMEOUT.as_secs(),
+        fallback.name()
+    );
+
+    let Some(choice) = read_line_with_timeout(PROMPT_TIMEOUT) else {
+        return fallback;
+    };
+
+    match parse_choice(&apps, &choice) {
+        Some(app) => {
+            if let Err(e) = store_app(app.name()) {
+                warn!("Could not store app selection in NVS: {:?}", e);
+            } else {
+                info!("Saved '{}' as the boot app", app.name());
+            }
+            app
+        }
+        None => {
+            warn!("Unknown app '{}', running {}", choice, fallback.name());
+            fallback
+        }
+    }
+}
+
+/// Resolves a menu choice given either as a 1-based index or an app name.
+fn parse_choice(apps: &[&'static dyn App], choice: &str) -> Option<&'static dyn App> {
+    match choice.parse::<usize>() {
+        Ok(index) => index.checked_sub(1).and_then(|i| apps.get(i)).copied(),
+        Err(_) => apps.iter().find(|app| app.name() == choice).copied(),
+    }
+}
+
+/// Reads a line from the console without blocking past `timeout`.
+/// Each key press restarts the timeout so slow typing is not cut off.
+fn read_line_with_timeout(timeout: Duration) -> Option<String> {
+    let mut stdin = std::io::stdin();
+    let mut line = String::new();
+    let mut byte = [0u8; 1];
+    let mut deadline = Instant::now() + timeout;
+
+    while Instant::now() < deadline {
+        match stdin.read(&mut byte) {
+            Ok(1) => {
+                deadline = Instant::now() + timeout;
+                match byte[0] {
+                    b'\r' | b'\n' if !line.is_empty() => return Some(line.trim().to_string()),
+                    b'\r' | b'\n' => {}
+                    b => line.push(b as char),
+                }
+            }
+            Ok(_) => thread::sleep(Duration::from_millis(10)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(10)),
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+fn open_nvs() -> Result<EspNvs<NvsDefault>> {
+    let partition = EspDefaultNvsPartition::take()?;
+    Ok(EspNvs::new(partition, NVS_NAMESPACE, true)?)
+}
+
+fn read_stored_app() -> Result<Option<String>> {
+    let nvs = open_nvs()?;
+    let mut buf = [0u8; 32];
+    Ok(nvs.get_str(NVS_APP_KEY, &mut buf)?.map(str::to_string))
+}
+
+fn store_app(name: &str) -> Result<()> {
+    let mut nvs = open_nvs()?;
+    nvs.set_str(NVS_APP_KEY, name)?;
+    Ok(())
+}
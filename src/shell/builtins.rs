@@ -0,0 +1,150 @@
+//! # shell::builtins
+//! Commands every shell has: `help`, `clear`, the variable commands `set`,
+//! `unset` and `env`, and `test`/`exit` for scripts.
+//!
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+use super::fs;
+use super::parser::is_valid_name;
+use super::{ArgSpec, CommandRegistry, Context, ExitStatus, FnCommand, Output};
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(FnCommand::new(
+        "help",
+        "List commands or describe one",
+        ArgSpec::new("[cmd]", 0, Some(1)),
+        help,
+    ));
+    registry.register(FnCommand::new(
+        "clear",
+        "Clear the screen",
+        ArgSpec::NONE,
+        clear,
+    ));
+    registry.register(FnCommand::new(
+        "set",
+        "Set a shell variable (use as $NAME)",
+        ArgSpec::new("<name> <value...>", 1, None),
+        set,
+    ));
+    registry.register(FnCommand::new(
+        "unset",
+        "Remove a shell variable",
+        ArgSpec::new("<name>", 1, Some(1)),
+        unset,
+    ));
+    registry.register(FnCommand::new(
+        "env",
+        "List shell variables",
+        ArgSpec::NONE,
+        env,
+    ));
+    registry.register(FnCommand::new(
+        "test",
+        "Compare values or check a file; exit status 0 if true",
+        ArgSpec::new(
+            "<a> <=|!=|-eq|-ne|-lt|-le|-gt|-ge> <b> | -e <path> | <text>",
+            1,
+            Some(3),
+        ),
+        test,
+    ));
+    registry.register(FnCommand::new(
+        "exit",
+        "Stop the script (or remote session) with a status",
+        ArgSpec::new("[status]", 0, Some(1)),
+        exit,
+    ));
+}
+
+/// `help [cmd]`: lists all commands, or shows usage and description of one.
+fn help(ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    match args.first() {
+        Some(name) => {
+            let command = ctx
+                .registry
+                .get(name)
+                .ok_or_else(|| anyhow!("no such command: {}", name))?;
+            out.write_line(&format!("{} {}", command.name(), command.args().usage));
+            out.write_line(&format!("  {}", command.help()));
+        }
+        None => {
+            let names: Vec<&str> = ctx.registry.iter().map(|command| command.name()).collect();
+            out.write_line(&names.join(", "));
+            out.write_line("Type 'help <cmd>' for details.");
+        }
+    }
+    Ok(())
+}
+
+fn clear(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    out.clear();
+    out.write_line("Cleared");
+    Ok(())
+}
+
+/// `set <name> <value...>`: the value is the remaining words joined by spaces.
+fn set(ctx: &mut Context, args: &[&str], _out: &mut dyn Output) -> Result<()> {
+    if !is_valid_name(args[0]) {
+        bail!("invalid name: {}", args[0]);
+    }
+    ctx.env.insert(args[0].to_string(), args[1..].join(" "));
+    Ok(())
+}
+
+fn unset(ctx: &mut Context, args: &[&str], _out: &mut dyn Output) -> Result<()> {
+    ctx.env.remove(args[0]);
+    Ok(())
+}
+
+fn env(ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    if ctx.env.is_empty() {
+        out.write_line("(no variables)");
+    }
+    for (name, value) in ctx.env.iter() {
+        out.write_line(&format!("{}={}", name, value));
+    }
+    Ok(())
+}
+
+/// `test`: fails silently with status 1 when the condition is false, for `if`.
+fn test(_ctx: &mut Context, args: &[&str], _out: &mut dyn Output) -> Result<()> {
+    let number = |value: &str| {
+        value
+            .parse::<i64>()
+            .map_err(|_| anyhow!("not a number: {}", value))
+    };
+    let result = match *args {
+        [text] => !text.is_empty(),
+        ["-e", path] => fs::resolve(path).is_ok_and(|path| Path::new(&path).exists()),
+        [a, "=", b] => a == b,
+        [a, "!=", b] => a != b,
+        [a, "-eq", b] => number(a)? == number(b)?,
+        [a, "-ne", b] => number(a)? != number(b)?,
+        [a, "-lt", b] => number(a)? < number(b)?,
+        [a, "-le", b] => number(a)? <= number(b)?,
+        [a, "-gt", b] => number(a)? > number(b)?,
+        [a, "-ge", b] => number(a)? >= number(b)?,
+        _ => bail!("invalid expression: {}", args.join(" ")),
+    };
+    if result {
+        Ok(())
+    } else {
+        Err(ExitStatus(1).into())
+    }
+}
+
+/// `exit [status]`: the status defaults to that of the last command.
+fn exit(ctx: &mut Context, args: &[&str], _out: &mut dyn Output) -> Result<()> {
+    let status = match args.first() {
+        Some(status) => status
+            .parse()
+            .map_err(|_| anyhow!("invalid status: {}", status))?,
+        None => ctx.status,
+    };
+    ctx.exit = Some(status);
+    Ok(())
+}
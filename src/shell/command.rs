@@ -0,0 +1,164 @@
+//! # shell::command
+//! The [`ShellCommand`] trait and the [`CommandRegistry`] commands are looked up in.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::{bail, Result};
+use log::warn;
+
+use super::Output;
+
+/// Accepted number of arguments and the usage string shown by `help <cmd>`.
+#[derive(Debug, Clone, Copy)]
+pub struct ArgSpec {
+    /// Argument synopsis, e.g. `<from> <to>`.
+    pub usage: &'static str,
+    pub min: usize,
+    /// `None` for commands taking any number of trailing arguments.
+    pub max: Option<usize>,
+}
+
+impl ArgSpec {
+    /// Command takes no arguments.
+    pub const NONE: ArgSpec = ArgSpec::new("", 0, Some(0));
+
+    pub const fn new(usage: &'static str, min: usize, max: Option<usize>) -> Self {
+        Self { usage, min, max }
+    }
+
+    /// Fails with a usage message if `args` doesn't fit the spec.
+    pub fn check(&self, name: &str, args: &[&str]) -> Result<()> {
+        let too_many = self.max.is_some_and(|max| args.len() > max);
+        if args.len() < self.min || too_many {
+            bail!("usage: {} {}", name, self.usage);
+        }
+        Ok(())
+    }
+}
+
+/// Shell variables, expanded with `$NAME` and edited with `set`/`unset`.
+pub type Env = BTreeMap<String, String>;
+
+/// State handed to a command while it runs.
+pub struct Context<'a> {
+    pub registry: &'a CommandRegistry,
+    pub env: &'a mut Env,
+    /// Exit status of the last command (`$?`).
+    pub status: i32,
+    /// Set by `exit`: stops the current line and the script running it.
+    pub exit: Option<i32>,
+    /// How many scripts are running inside each other (see `run`).
+    pub depth: usize,
+}
+
+/// Error that sets the exit status without printing a message, for commands whose
+/// failure is an answer rather than a problem (a false `test`, a failed script).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl std::error::Error for ExitStatus {}
+
+/// A command that can be registered with a [`CommandRegistry`].
+pub trait ShellCommand: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// One-line description shown by `help <cmd>`.
+    fn help(&self) -> &str;
+
+    fn args(&self) -> ArgSpec {
+        ArgSpec::NONE
+    }
+
+    /// Runs the command. `args` excludes the command name and has been checked against [`Self::args`].
+    fn run(&self, ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()>;
+}
+
+/// Handler signature used by [`FnCommand`].
+pub type CommandFn = fn(&mut Context, &[&str], &mut dyn Output) -> Result<()>;
+
+/// A [`ShellCommand`] backed by a plain function, for commands without state of their own.
+pub struct FnCommand {
+    name: &'static str,
+    help: &'static str,
+    args: ArgSpec,
+    handler: CommandFn,
+}
+
+impl FnCommand {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        args: ArgSpec,
+        handler: CommandFn,
+    ) -> Self {
+        Self {
+            name,
+            help,
+            args,
+            handler,
+        }
+    }
+}
+
+impl ShellCommand for FnCommand {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn help(&self) -> &str {
+        self.help
+    }
+
+    fn args(&self) -> ArgSpec {
+        self.args
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+        (self.handler)(ctx, args, out)
+    }
+}
+
+/// Commands by name. Modules contribute commands with [`CommandRegistry::register`]
+/// before the registry is handed to a shell.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Box<dyn ShellCommand>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a command, replacing any earlier command with the same name.
+    pub fn register(&mut self, command: impl ShellCommand + 'static) {
+        let name = command.name().to_string();
+        if self
+            .commands
+            .insert(name.clone(), Box::new(command))
+            .is_some()
+        {
+            warn!(
+                "Shell command '{}' registered twice, keeping the last one",
+                name
+            );
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn ShellCommand> {
+        self.commands.get(name).map(|command| command.as_ref())
+    }
+
+    /// All commands in name order.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn ShellCommand> {
+        self.commands.values().map(|command| command.as_ref())
+    }
+}
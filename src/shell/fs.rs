@@ -0,0 +1,316 @@
+//! # shell::fs
+//! File commands operating on the flash filesystem mounted at [`FS_MOUNT_POINT`].
+//!
+//! Every path argument goes through [`resolve`], which interprets it relative to the
+//! mount point and rejects anything that would escape it (`../..`, `/etc`, ...).
+//!
+//! SPIFFS has no directories, so there is no `mkdir`/`rmdir`: a `/` in a file
+//! name is just part of the name.
+//!
+
+use std::fs;
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, bail, Result};
+
+use super::{ArgSpec, CommandRegistry, Context, FnCommand, Output};
+use crate::storage::{self, FS_MOUNT_POINT};
+
+/// Bytes shown by `hexdump` when no length is given.
+const HEXDUMP_DEFAULT_LEN: u64 = 256;
+/// Bytes per `hexdump` row; 8 keeps a row within the 38 columns of the LCD log.
+const HEXDUMP_ROW: usize = 8;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(FnCommand::new(
+        "ls",
+        "List a directory",
+        ArgSpec::new("[path]", 0, Some(1)),
+        ls,
+    ));
+    registry.register(FnCommand::new(
+        "cat",
+        "Print a text file",
+        ArgSpec::new("<file>", 1, Some(1)),
+        cat,
+    ));
+    registry.register(FnCommand::new(
+        "echo",
+        "Print text or write it to a file",
+        ArgSpec::new("<text...> [> file | >> file]", 0, None),
+        echo,
+    ));
+    registry.register(FnCommand::new(
+        "append",
+        "Append a line to a file",
+        ArgSpec::new("<file> <text...>", 1, None),
+        append,
+    ));
+    registry.register(FnCommand::new(
+        "rm",
+        "Delete a file",
+        ArgSpec::new("<file>", 1, Some(1)),
+        rm,
+    ));
+    registry.register(FnCommand::new(
+        "mv",
+        "Rename a file",
+        ArgSpec::new("<from> <to>", 2, Some(2)),
+        mv,
+    ));
+    registry.register(FnCommand::new(
+        "cp",
+        "Copy a file",
+        ArgSpec::new("<from> <to>", 2, Some(2)),
+        cp,
+    ));
+    registry.register(FnCommand::new(
+        "hexdump",
+        "Dump file bytes as hex and ASCII",
+        ArgSpec::new("<file> [len]", 1, Some(2)),
+        hexdump,
+    ));
+    registry.register(FnCommand::new(
+        "df",
+        "Show filesystem usage",
+        ArgSpec::NONE,
+        df,
+    ));
+}
+
+/// Normalizes `path` against [`FS_MOUNT_POINT`].
+///
+/// Relative paths and absolute paths are both rooted at the mount point
+/// (`/spiffs/a` and `a` are the same file). `.` and `..` are resolved
+/// lexically; `..` above the mount point is an error.
+pub fn resolve(path: &str) -> Result<String> {
+    let rest = path
+        .strip_prefix(FS_MOUNT_POINT)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .unwrap_or(path);
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    bail!("{}: outside {}", path, FS_MOUNT_POINT);
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+
+    let mut resolved = String::from(FS_MOUNT_POINT);
+    for part in parts {
+        resolved.push('/');
+        resolved.push_str(part);
+    }
+    Ok(resolved)
+}
+
+/// Tab-completion candidates for a partially typed path, keeping the typed directory part.
+/// Directories get a trailing `/`.
+pub fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let Some(entries) = resolve(dir).ok().and_then(|path| fs::read_dir(path).ok()) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// `ls [path]`: lists a directory.
+fn ls(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let path = resolve(args.first().copied().unwrap_or(""))?;
+    out.write_line(&format!("Listing {}", path));
+    let mut any = false;
+    for entry in fs::read_dir(&path)?.flatten() {
+        let name = entry.file_name().into_string().unwrap_or("?".into());
+        let size = entry
+            .metadata()
+            .map(|m| {
+                if m.is_dir() {
+                    "[DIR]".into()
+                } else {
+                    format!("{}B", m.len())
+                }
+            })
+            .unwrap_or_else(|_| "?".into());
+        out.write_line(&format!("- {} ({})", name, size));
+        any = true;
+    }
+    if !any {
+        out.write_line("(empty)");
+    }
+    Ok(())
+}
+
+/// `cat <file>`: prints a text file line by line.
+fn cat(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let path = resolve(args[0])?;
+    let bytes = fs::read(&path)?;
+    for line in String::from_utf8_lossy(&bytes).lines() {
+        out.write_line(line);
+    }
+    Ok(())
+}
+
+/// `echo <text...> [> file | >> file]`: prints text or writes it to a file.
+fn echo(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let redirect = args.iter().position(|a| *a == ">" || *a == ">>");
+    let Some(at) = redirect else {
+        out.write_line(&args.join(" "));
+        return Ok(());
+    };
+
+    let file = match &args[at + 1..] {
+        [file] => resolve(file)?,
+        _ => bail!("usage: echo <text> > <file>"),
+    };
+    let text = format!("{}\n", args[..at].join(" "));
+    if args[at] == ">>" {
+        append_to(&file, &text)?;
+    } else {
+        fs::write(&file, text)?;
+    }
+    Ok(())
+}
+
+/// `append <file> <text...>`: appends a line to a file, creating it if needed.
+fn append(_ctx: &mut Context, args: &[&str], _out: &mut dyn Output) -> Result<()> {
+    let file = resolve(args[0])?;
+    append_to(&file, &format!("{}\n", args[1..].join(" ")))
+}
+
+/// `rm <file>`: deletes a file.
+fn rm(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let path = resolve(args[0])?;
+    fs::remove_file(&path)?;
+    out.write_line(&format!("Removed {}", path));
+    Ok(())
+}
+
+/// `mv <from> <to>`: renames a file.
+fn mv(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let from = resolve(args[0])?;
+    let to = resolve(args[1])?;
+    fs::rename(&from, &to)?;
+    out.write_line(&format!("{} -> {}", from, to));
+    Ok(())
+}
+
+/// `cp <from> <to>`: copies a file.
+fn cp(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let from = resolve(args[0])?;
+    let to = resolve(args[1])?;
+    let copied = fs::copy(&from, &to)?;
+    out.write_line(&format!("{} -> {} ({}B)", from, to, copied));
+    Ok(())
+}
+
+/// `hexdump <file> [len]`: dumps the first `len` bytes (default 256) as hex and ASCII.
+fn hexdump(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let path = resolve(args[0])?;
+    let len = match args.get(1) {
+        Some(len) => len
+            .parse()
+            .map_err(|_| anyhow!("invalid length: {}", len))?,
+        None => HEXDUMP_DEFAULT_LEN,
+    };
+
+    let file = fs::File::open(&path)?;
+    // The length is whatever was typed; never read past the end of the file.
+    let len = len.min(file.metadata()?.len());
+    let mut data = Vec::new();
+    file.take(len).read_to_end(&mut data)?;
+
+    for (row, chunk) in data.chunks(HEXDUMP_ROW).enumerate() {
+        let mut line = format!("{:04x}:", row * HEXDUMP_ROW);
+        for byte in chunk {
+            line.push_str(&format!(" {:02x}", byte));
+        }
+        line.push_str(&"   ".repeat(HEXDUMP_ROW - chunk.len()));
+        line.push(' ');
+        line.extend(
+            chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' }),
+        );
+        out.write_line(&line);
+    }
+    if data.is_empty() {
+        out.write_line("(empty)");
+    }
+    Ok(())
+}
+
+/// `df`: shows filesystem usage.
+fn df(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let usage = storage::usage()?;
+    out.write_line(&format!("{}: {}B total", FS_MOUNT_POINT, usage.total));
+    out.write_line(&format!(
+        "{}B used, {}B free",
+        usage.used,
+        usage.total.saturating_sub(usage.used)
+    ));
+    Ok(())
+}
+
+fn append_to(path: &str, text: &str) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_and_absolute_paths_are_rooted_at_the_mount_point() {
+        assert_eq!(resolve("a.txt").unwrap(), "/spiffs/a.txt");
+        assert_eq!(resolve("/a.txt").unwrap(), "/spiffs/a.txt");
+        assert_eq!(resolve("/spiffs/a.txt").unwrap(), "/spiffs/a.txt");
+        assert_eq!(resolve("/spiffs").unwrap(), "/spiffs");
+        assert_eq!(resolve("").unwrap(), "/spiffs");
+        // Only a whole `/spiffs` component is the mount point.
+        assert_eq!(resolve("/spiffsy/a").unwrap(), "/spiffs/spiffsy/a");
+    }
+
+    #[test]
+    fn dots_are_resolved() {
+        assert_eq!(resolve("./a//b/./c").unwrap(), "/spiffs/a/b/c");
+        assert_eq!(resolve("a/b/../c").unwrap(), "/spiffs/a/c");
+        assert_eq!(resolve("/spiffs/a/..").unwrap(), "/spiffs");
+    }
+
+    #[test]
+    fn paths_above_the_mount_point_are_rejected() {
+        for path in ["..", "/spiffs/..", "a/../../b", "/spiffs/../etc/passwd"] {
+            assert_eq!(
+                resolve(path).unwrap_err().to_string(),
+                format!("{}: outside /spiffs", path)
+            );
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! # shell::hw
+//! Hardware commands: `gpio`, `adc` and `pwm`.
+//!
+//! Pins are ESP32 GPIO numbers. Pins reserved by the board (display/SPI bus, flash,
+//! console; see [`board::reserved_pins`]) can be read but never reconfigured.
+//!
+
+use std::collections::BTreeMap;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use anyhow::{anyhow, bail, Result};
+use esp_idf_sys::{self as sys, esp};
+
+use super::{ArgSpec, CommandRegistry, Context, FnCommand, Output};
+use crate::board;
+
+const GPIO_USAGE: &str = "<read|write|mode|reserved> [pin] [level|mode]";
+const ADC_USAGE: &str = "read <channel>";
+const PWM_USAGE: &str = "[<pin> <freq> <duty%> | <pin> off]";
+
+/// GPIO of each ADC1 channel. ADC2 is shared with Wi-Fi and not offered.
+const ADC1_PINS: [i32; 8] = [36, 37, 38, 39, 32, 33, 34, 35];
+
+/// LEDC channels used by `pwm`; each has its own timer so frequencies are independent.
+const PWM_CHANNELS: usize = 4;
+const PWM_MAX_FREQ_HZ: u32 = 1_000_000;
+const PWM_MODE: sys::ledc_mode_t = sys::ledc_mode_t_LEDC_LOW_SPEED_MODE;
+/// Source clock of the LEDC timers, which bounds the duty resolution.
+const LEDC_CLK_HZ: u32 = 80_000_000;
+const LEDC_MAX_RESOLUTION_BITS: u32 = 13;
+
+/// Pin configuration selected with `gpio mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PinMode {
+    Input,
+    PullUp,
+    PullDown,
+    Output,
+    OpenDrain,
+}
+
+impl PinMode {
+    const NAMES: &'static str = "in|in-pullup|in-pulldown|out|od";
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "in" => Some(Self::Input),
+            "in-pullup" => Some(Self::PullUp),
+            "in-pulldown" => Some(Self::PullDown),
+            "out" => Some(Self::Output),
+            "od" => Some(Self::OpenDrain),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Input => "in",
+            Self::PullUp => "in-pullup",
+            Self::PullDown => "in-pulldown",
+            Self::Output => "out",
+            Self::OpenDrain => "od",
+        }
+    }
+
+    fn is_output(self) -> bool {
+        matches!(self, Self::Output | Self::OpenDrain)
+    }
+
+    /// Outputs keep their input enabled so `gpio read` shows the driven level.
+    fn gpio_config(self) -> (sys::gpio_mode_t, sys::gpio_pull_mode_t) {
+        match self {
+            Self::Input => (
+                sys::gpio_mode_t_GPIO_MODE_INPUT,
+                sys::gpio_pull_mode_t_GPIO_FLOATING,
+            ),
+            Self::PullUp => (
+                sys::gpio_mode_t_GPIO_MODE_INPUT,
+                sys::gpio_pull_mode_t_GPIO_PULLUP_ONLY,
+            ),
+            Self::PullDown => (
+                sys::gpio_mode_t_GPIO_MODE_INPUT,
+                sys::gpio_pull_mode_t_GPIO_PULLDOWN_ONLY,
+            ),
+            Self::Output => (
+                sys::gpio_mode_t_GPIO_MODE_INPUT_OUTPUT,
+                sys::gpio_pull_mode_t_GPIO_FLOATING,
+            ),
+            Self::OpenDrain => (
+                sys::gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD,
+                sys::gpio_pull_mode_t_GPIO_PULLUP_ONLY,
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PwmOutput {
+    pin: i32,
+    freq_hz: u32,
+    duty_percent: u32,
+}
+
+/// Pins configured from the shell, so `gpio` and `pwm` don't fight over a pin.
+struct PinState {
+    modes: BTreeMap<i32, PinMode>,
+    pwm: [Option<PwmOutput>; PWM_CHANNELS],
+}
+
+impl PinState {
+    fn pwm_channel(&self, pin: i32) -> Option<usize> {
+        self.pwm
+            .iter()
+            .position(|output| output.is_some_and(|output| output.pin == pin))
+    }
+}
+
+static PINS: Mutex<PinState> = Mutex::new(PinState {
+    modes: BTreeMap::new(),
+    pwm: [None; PWM_CHANNELS],
+});
+
+fn pins() -> MutexGuard<'static, PinState> {
+    PINS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(FnCommand::new(
+        "gpio",
+        "Read, drive or configure a GPIO, or list reserved pins",
+        ArgSpec::new(GPIO_USAGE, 1, Some(3)),
+        gpio,
+    ));
+    registry.register(FnCommand::new(
+        "adc",
+        "Read an ADC1 channel (raw 12-bit value)",
+        ArgSpec::new(ADC_USAGE, 2, Some(2)),
+        adc,
+    ));
+    registry.register(FnCommand::new(
+        "pwm",
+        "Start/stop PWM on a pin, or list PWM outputs",
+        ArgSpec::new(PWM_USAGE, 0, Some(3)),
+        pwm,
+    ));
+}
+
+/// Parses a GPIO number (`4` or `gpio4`) that exists on the ESP32.
+fn parse_pin(arg: &str) -> Result<i32> {
+    let pin: i32 = arg
+        .strip_prefix("gpio")
+        .unwrap_or(arg)
+        .parse()
+        .map_err(|_| anyhow!("invalid pin: {}", arg))?;
+    if !matches!(pin, 0..=19 | 21..=23 | 25..=27 | 32..=39) {
+        bail!("GPIO{} does not exist", pin);
+    }
+    Ok(pin)
+}
+
+/// Parses a pin the shell may reconfigure.
+fn parse_free_pin(arg: &str) -> Result<i32> {
+    let pin = parse_pin(arg)?;
+    if let Some(owner) = board::reserved_pin(pin) {
+        bail!("GPIO{} is reserved ({})", pin, owner);
+    }
+    Ok(pin)
+}
+
+fn check_output_capable(pin: i32) -> Result<()> {
+    if pin >= 34 {
+        bail!("GPIO{} is input-only", pin);
+    }
+    Ok(())
+}
+
+fn configure(state: &mut PinState, pin: i32, mode: PinMode) -> Result<()> {
+    if state.pwm_channel(pin).is_some() {
+        bail!(
+            "GPIO{} is driven by PWM; stop it with 'pwm {} off'",
+            pin,
+            pin
+        );
+    }
+    if mode.is_output() {
+        check_output_capable(pin)?;
+    }
+    let (direction, pull) = mode.gpio_config();
+    esp!(unsafe { sys::gpio_reset_pin(pin) })?;
+    esp!(unsafe { sys::gpio_set_direction(pin, direction) })?;
+    esp!(unsafe { sys::gpio_set_pull_mode(pin, pull) })?;
+    state.modes.insert(pin, mode);
+    Ok(())
+}
+
+/// `gpio read|write|mode|reserved`. Reading or writing a pin the shell hasn't
+/// configured yet switches it to `in` or `out` first.
+fn gpio(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    match (args[0], &args[1..]) {
+        ("read", [pin]) => {
+            let pin = parse_pin(pin)?;
+            if board::reserved_pin(pin).is_none() {
+                let mut state = pins();
+                if !state.modes.contains_key(&pin) && state.pwm_channel(pin).is_none() {
+                    configure(&mut state, pin, PinMode::Input)?;
+                }
+            }
+            let level = unsafe { sys::gpio_get_level(pin) };
+            out.write_line(&format!("GPIO{} = {}", pin, level));
+        }
+        ("write", [pin, level]) => {
+            let pin = parse_free_pin(pin)?;
+            let level: u32 = match *level {
+                "0" | "low" => 0,
+                "1" | "high" => 1,
+                other => bail!("invalid level: {} (0|1|low|high)", other),
+            };
+            let mut state = pins();
+            if !state.modes.get(&pin).is_some_and(|mode| mode.is_output()) {
+                configure(&mut state, pin, PinMode::Output)?;
+            }
+            esp!(unsafe { sys::gpio_set_level(pin, level) })?;
+        }
+        ("mode", [pin]) => {
+            let pin = parse_pin(pin)?;
+            let state = pins();
+            let mode = match (state.modes.get(&pin), state.pwm_channel(pin)) {
+                (_, Some(_)) => "pwm",
+                (Some(mode), None) => mode.name(),
+                (None, None) => board::reserved_pin(pin).unwrap_or("not configured"),
+            };
+            out.write_line(&format!("GPIO{}: {}", pin, mode));
+        }
+        ("mode", [pin, mode]) => {
+            let pin = parse_free_pin(pin)?;
+            let mode = PinMode::parse(mode)
+                .ok_or_else(|| anyhow!("invalid mode: {} ({})", mode, PinMode::NAMES))?;
+            configure(&mut pins(), pin, mode)?;
+        }
+        ("reserved", []) => {
+            for (pin, owner) in board::reserved_pins() {
+                out.write_line(&format!("GPIO{:<2} {}", pin, owner));
+            }
+        }
+        _ => bail!("usage: gpio {}", GPIO_USAGE),
+    }
+    Ok(())
+}
+
+/// `adc read <channel>`: one-shot conversion on ADC1 at 12 dB attenuation
+/// (roughly 0-3.1 V). The unit is released again so apps can use it.
+fn adc(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    if args[0] != "read" {
+        bail!("usage: adc {}", ADC_USAGE);
+    }
+    let channel: usize = args[1]
+        .parse()
+        .ok()
+        .filter(|channel| *channel < ADC1_PINS.len())
+        .ok_or_else(|| anyhow!("channel must be 0..{}", ADC1_PINS.len() - 1))?;
+    let pin = ADC1_PINS[channel];
+    if let Some(owner) = board::reserved_pin(pin) {
+        bail!("GPIO{} is reserved ({})", pin, owner);
+    }
+
+    let unit_cfg = sys::adc_oneshot_unit_init_cfg_t {
+        unit_id: sys::adc_unit_t_ADC_UNIT_1,
+        ulp_mode: sys::adc_ulp_mode_t_ADC_ULP_MODE_DISABLE,
+        ..Default::default()
+    };
+    let chan_cfg = sys::adc_oneshot_chan_cfg_t {
+        atten: sys::adc_atten_t_ADC_ATTEN_DB_12,
+        bitwidth: sys::adc_bitwidth_t_ADC_BITWIDTH_12,
+    };
+    let mut unit = ptr::null_mut();
+    esp!(unsafe { sys::adc_oneshot_new_unit(&unit_cfg, &mut unit) })
+        .map_err(|e| anyhow!("ADC1 unavailable: {}", e))?;
+    let mut raw = 0;
+    let result = esp!(unsafe { sys::adc_oneshot_config_channel(unit, channel as _, &chan_cfg) })
+        .and_then(|_| esp!(unsafe { sys::adc_oneshot_read(unit, channel as _, &mut raw) }));
+    unsafe { sys::adc_oneshot_del_unit(unit) };
+    result?;
+
+    out.write_line(&format!("ADC1 ch{} (GPIO{}): {}", channel, pin, raw));
+    Ok(())
+}
+
+/// `pwm <pin> <freq> <duty%>` starts or updates a PWM output, `pwm <pin> off`
+/// stops it and releases the pin, `pwm` lists the active outputs.
+fn pwm(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let mut state = pins();
+    match args {
+        [] => {
+            let active: Vec<_> = state.pwm.iter().flatten().collect();
+            if active.is_empty() {
+                out.write_line("(no PWM outputs)");
+            }
+            for output in active {
+                out.write_line(&format!(
+                    "GPIO{}: {} Hz, {}%",
+                    output.pin, output.freq_hz, output.duty_percent
+                ));
+            }
+        }
+        [pin, "off"] => {
+            let pin = parse_free_pin(pin)?;
+            let channel = state
+                .pwm_channel(pin)
+                .ok_or_else(|| anyhow!("no PWM on GPIO{}", pin))?;
+            esp!(unsafe { sys::ledc_stop(PWM_MODE, channel as _, 0) })?;
+            esp!(unsafe { sys::gpio_reset_pin(pin) })?;
+            state.pwm[channel] = None;
+        }
+        [pin, freq, duty] => {
+            let pin = parse_free_pin(pin)?;
+            check_output_capable(pin)?;
+            let freq_hz: u32 = freq
+                .parse()
+                .ok()
+                .filter(|freq| (1..=PWM_MAX_FREQ_HZ).contains(freq))
+                .ok_or_else(|| anyhow!("frequency must be 1..{} Hz", PWM_MAX_FREQ_HZ))?;
+            let duty_percent: u32 = duty
+                .trim_end_matches('%')
+                .parse()
+                .ok()
+                .filter(|duty| *duty <= 100)
+                .ok_or_else(|| anyhow!("duty must be 0..100%"))?;
+
+            let channel = match state.pwm_channel(pin) {
+                Some(channel) => channel,
+                None => state
+                    .pwm
+                    .iter()
+                    .position(Option::is_none)
+                    .ok_or_else(|| anyhow!("all {} PWM channels in use", PWM_CHANNELS))?,
+            };
+            start_pwm(channel, pin, freq_hz, duty_percent)?;
+            state.modes.remove(&pin);
+            state.pwm[channel] = Some(PwmOutput {
+                pin,
+                freq_hz,
+                duty_percent,
+            });
+        }
+        _ => bail!("usage: pwm {}", PWM_USAGE),
+    }
+    Ok(())
+}
+
+/// Configures LEDC timer and channel `channel` with the highest duty resolution
+/// the frequency allows.
+fn start_pwm(channel: usize, pin: i32, freq_hz: u32, duty_percent: u32) -> Result<()> {
+    let resolution_bits = (LEDC_CLK_HZ / freq_hz)
+        .ilog2()
+        .min(LEDC_MAX_RESOLUTION_BITS);
+    let timer_cfg = sys::ledc_timer_config_t {
+        speed_mode: PWM_MODE,
+        duty_resolution: resolution_bits,
+        timer_num: channel as _,
+        freq_hz,
+        clk_cfg: sys::ledc_clk_cfg_t_LEDC_AUTO_CLK,
+        ..Default::default()
+    };
+    esp!(unsafe { sys::ledc_timer_config(&timer_cfg) })?;
+
+    let duty = ((1u32 << resolution_bits) * duty_percent) / 100;
+    let channel_cfg = sys::ledc_channel_config_t {
+        gpio_num: pin,
+        speed_mode: PWM_MODE,
+        channel: channel as _,
+        intr_type: sys::ledc_intr_type_t_LEDC_INTR_DISABLE,
+        timer_sel: channel as _,
+        duty,
+        hpoint: 0,
+        ..Default::default()
+    };
+    esp!(unsafe { sys::ledc_channel_config(&channel_cfg) })?;
+    Ok(())
+}
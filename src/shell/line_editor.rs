@@ -0,0 +1,442 @@
+//! # shell::line_editor
+//! VT100 line editing for serial terminals.
+//!
+//! [`LineEditor`] is fed one input byte at a time and appends the escape sequences
+//! needed to update the terminal to an output string, so it is independent of the
+//! UART and can be driven on the host. Supported keys:
+//! - Left/Right, Home/End, Ctrl-A/Ctrl-E, Ctrl-B/Ctrl-F: move the cursor
+//! - Backspace, Delete, Ctrl-U (kill to start), Ctrl-K (kill to end)
+//! - Up/Down, Ctrl-P/Ctrl-N: browse history
+//! - Tab: complete via a [`Completer`]
+//! - Ctrl-C: discard the line
+//!
+
+use std::fs;
+
+/// Oldest entries are dropped beyond this many.
+pub const MAX_HISTORY: usize = 32;
+
+/// Candidates for the word ending at the cursor.
+#[derive(Debug, Default)]
+pub struct Completion {
+    /// Byte offset in the line where the completed word starts.
+    pub start: usize,
+    /// Full replacements for `line[start..cursor]`. Entries ending in `/` are directories.
+    pub candidates: Vec<String>,
+}
+
+/// Supplies tab-completion candidates.
+pub trait Completer {
+    /// `line` is the input up to the cursor.
+    fn complete(&self, line: &str) -> Completion;
+}
+
+/// Progress through a multi-byte escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// Got ESC.
+    Start,
+    /// Got `ESC [` or `ESC O`, collecting parameter digits.
+    Csi(u8),
+}
+
+/// Editable input line with history.
+pub struct LineEditor {
+    prompt: String,
+    line: String,
+    /// Cursor position as a byte offset; input is restricted to ASCII.
+    cursor: usize,
+    history: Vec<String>,
+    /// Index into `history` while browsing, `None` when editing a new line.
+    history_pos: Option<usize>,
+    /// Line being typed before history browsing started.
+    draft: String,
+    escape: Escape,
+    /// The last byte was `\r`, so a `\n` right after it belongs to the same Enter.
+    after_cr: bool,
+}
+
+impl LineEditor {
+    pub fn new(prompt: &str) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            line: String::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_pos: None,
+            draft: String::new(),
+            escape: Escape::None,
+            after_cr: false,
+        }
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn set_history(&mut self, mut history: Vec<String>) {
+        let excess = history.len().saturating_sub(MAX_HISTORY);
+        history.drain(..excess);
+        self.history = history;
+    }
+
+    /// Processes one input byte. Returns the finished line when Enter is pressed.
+    pub fn feed(
+        &mut self,
+        byte: u8,
+        completer: &dyn Completer,
+        term: &mut String,
+    ) -> Option<String> {
+        // Terminals send Enter as `\r`, `\n` or `\r\n`; the last must submit only once.
+        if std::mem::replace(&mut self.after_cr, byte == b'\r') && byte == b'\n' {
+            return None;
+        }
+        match self.escape {
+            Escape::Start => {
+                self.escape = match byte {
+                    b'[' | b'O' => Escape::Csi(0),
+                    _ => Escape::None,
+                };
+                return None;
+            }
+            Escape::Csi(param) => {
+                if byte.is_ascii_digit() {
+                    self.escape = Escape::Csi(param.saturating_mul(10).saturating_add(byte - b'0'));
+                    return None;
+                }
+                self.escape = Escape::None;
+                match (byte, param) {
+                    (b'A', _) => self.history_prev(term),
+                    (b'B', _) => self.history_next(term),
+                    (b'C', _) => self.move_to(self.cursor + 1, term),
+                    (b'D', _) => self.move_to(self.cursor.saturating_sub(1), term),
+                    (b'H', _) | (b'~', 1 | 7) => self.move_to(0, term),
+                    (b'F', _) | (b'~', 4 | 8) => self.move_to(self.line.len(), term),
+                    (b'~', 3) => self.delete(term),
+                    _ => {}
+                }
+                return None;
+            }
+            Escape::None => {}
+        }
+
+        match byte {
+            b'\r' | b'\n' => return Some(self.submit(term)),
+            0x1b => self.escape = Escape::Start,
+            0x01 => self.move_to(0, term),
+            0x05 => self.move_to(self.line.len(), term),
+            0x02 => self.move_to(self.cursor.saturating_sub(1), term),
+            0x06 => self.move_to(self.cursor + 1, term),
+            0x10 => self.history_prev(term),
+            0x0e => self.history_next(term),
+            0x08 | 0x7f if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+                self.refresh(term);
+            }
+            0x0b => {
+                self.line.truncate(self.cursor);
+                self.refresh(term);
+            }
+            0x15 => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+                self.refresh(term);
+            }
+            0x03 => {
+                term.push_str("^C\r\n");
+                self.reset();
+                term.push_str(&self.prompt);
+            }
+            b'\t' => self.complete(completer, term),
+            0x20..=0x7e => {
+                self.line.insert(self.cursor, byte as char);
+                self.cursor += 1;
+                self.refresh(term);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Ends the current line, records it in history and starts a new one.
+    fn submit(&mut self, term: &mut String) -> String {
+        term.push_str("\r\n");
+        let line = std::mem::take(&mut self.line);
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.reset();
+        line
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.history_pos = None;
+        self.draft.clear();
+    }
+
+    fn delete(&mut self, term: &mut String) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+            self.refresh(term);
+        }
+    }
+
+    fn move_to(&mut self, cursor: usize, term: &mut String) {
+        let cursor = cursor.min(self.line.len());
+        if cursor != self.cursor {
+            self.cursor = cursor;
+            self.refresh(term);
+        }
+    }
+
+    fn history_prev(&mut self, term: &mut String) {
+        let pos = match self.history_pos {
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.line.clone();
+                self.history.len() - 1
+            }
+            Some(0) => return,
+            Some(pos) => pos - 1,
+        };
+        self.history_pos = Some(pos);
+        self.replace_line(self.history[pos].clone(), term);
+    }
+
+    fn history_next(&mut self, term: &mut String) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        if pos + 1 < self.history.len() {
+            self.history_pos = Some(pos + 1);
+            self.replace_line(self.history[pos + 1].clone(), term);
+        } else {
+            self.history_pos = None;
+            let draft = std::mem::take(&mut self.draft);
+            self.replace_line(draft, term);
+        }
+    }
+
+    fn replace_line(&mut self, line: String, term: &mut String) {
+        self.line = line;
+        self.cursor = self.line.len();
+        self.refresh(term);
+    }
+
+    fn complete(&mut self, completer: &dyn Completer, term: &mut String) {
+        let completion = completer.complete(&self.line[..self.cursor]);
+        let start = completion.start.min(self.cursor);
+        let candidates = completion.candidates;
+
+        let insert = match candidates.as_slice() {
+            [] => return,
+            [only] if only.ends_with('/') => only.clone(),
+            [only] => format!("{} ", only),
+            [first, rest @ ..] => {
+                // Compared by char, so the prefix ends on a char boundary even
+                // for non-ASCII file names.
+                let common = rest.iter().fold(first.len(), |len, c| {
+                    first
+                        .char_indices()
+                        .zip(c.chars())
+                        .take_while(|((at, a), b)| *at < len && a == b)
+                        .last()
+                        .map_or(0, |((at, a), _)| at + a.len_utf8())
+                });
+                if common <= self.cursor - start {
+                    // Nothing more to add: show the choices and redraw the line below them.
+                    term.push_str("\r\n");
+                    term.push_str(&candidates.join("  "));
+                    term.push_str("\r\n");
+                    self.refresh(term);
+                    return;
+                }
+                first[..common].to_string()
+            }
+        };
+
+        if !insert.is_ascii() {
+            return;
+        }
+        self.line.replace_range(start..self.cursor, &insert);
+        self.cursor = start + insert.len();
+        self.refresh(term);
+    }
+
+    /// Redraws prompt and line, then places the cursor.
+    fn refresh(&self, term: &mut String) {
+        term.push('\r');
+        term.push_str(&self.prompt);
+        term.push_str(&self.line);
+        term.push_str("\x1b[K");
+        let back = self.line.len() - self.cursor;
+        if back > 0 {
+            term.push_str(&format!("\x1b[{}D", back));
+        }
+    }
+}
+
+/// Reads a history file (one entry per line); a missing file gives an empty history.
+pub fn load_history(path: &str) -> Vec<String> {
+    fs::read_to_string(path)
+        .map(|text| text.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Writes the history to `path`, one entry per line.
+pub fn save_history(path: &str, history: &[String]) -> std::io::Result<()> {
+    let mut text = history.join("\n");
+    text.push('\n');
+    fs::write(path, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Completes the last word from a fixed list of names.
+    struct Names(&'static [&'static str]);
+
+    impl Completer for Names {
+        fn complete(&self, line: &str) -> Completion {
+            let start = line.rfind(' ').map_or(0, |space| space + 1);
+            Completion {
+                start,
+                candidates: self
+                    .0
+                    .iter()
+                    .filter(|name| name.starts_with(&line[start..]))
+                    .map(|name| name.to_string())
+                    .collect(),
+            }
+        }
+    }
+
+    const NAMES: Names = Names(&["help", "hexdump", "history", "logs/"]);
+
+    /// Feeds `input` and returns the lines it submitted.
+    fn feed(editor: &mut LineEditor, input: &[u8]) -> Vec<String> {
+        let mut term = String::new();
+        input
+            .iter()
+            .filter_map(|&byte| editor.feed(byte, &NAMES, &mut term))
+            .collect()
+    }
+
+    /// Feeds `input` and returns the line being edited, with `|` at the cursor.
+    fn edit(editor: &mut LineEditor, input: &[u8]) -> String {
+        assert_eq!(feed(editor, input), Vec::<String>::new());
+        let mut line = editor.line.clone();
+        line.insert(editor.cursor, '|');
+        line
+    }
+
+    fn new() -> LineEditor {
+        LineEditor::new("> ")
+    }
+
+    #[test]
+    fn cr_lf_and_crlf_each_submit_once() {
+        let mut editor = new();
+        assert_eq!(
+            feed(&mut editor, b"a\rb\nc\r\nd\r\r\n"),
+            ["a", "b", "c", "d", ""]
+        );
+        assert_eq!(feed(&mut editor, b"\n\n"), ["", ""]);
+    }
+
+    #[test]
+    fn keys_move_the_cursor_and_edit() {
+        let mut editor = new();
+        assert_eq!(edit(&mut editor, b"helo"), "helo|");
+        assert_eq!(edit(&mut editor, b"\x1b[Dl"), "hell|o");
+        assert_eq!(edit(&mut editor, b"\x1b[H"), "|hello");
+        assert_eq!(edit(&mut editor, b"\x06\x06"), "he|llo");
+        assert_eq!(edit(&mut editor, b"\x1b[3~"), "he|lo");
+        assert_eq!(edit(&mut editor, b"\x7f"), "h|lo");
+        assert_eq!(edit(&mut editor, b"\x1b[F\x02"), "hl|o");
+        assert_eq!(edit(&mut editor, b"\x0b"), "hl|");
+        assert_eq!(edit(&mut editor, b"\x01\x1b[C\x15"), "|l");
+        assert_eq!(edit(&mut editor, b"\x7f\x1bOF"), "l|");
+        assert_eq!(edit(&mut editor, b"\x03"), "|");
+    }
+
+    #[test]
+    fn history_is_browsed_and_the_draft_kept() {
+        let mut editor = new();
+        feed(&mut editor, b"one\rtwo\rtwo\r  \r");
+        assert_eq!(editor.history(), ["one", "two"]);
+        assert_eq!(edit(&mut editor, b"dra"), "dra|");
+        assert_eq!(edit(&mut editor, b"\x1b[A"), "two|");
+        assert_eq!(edit(&mut editor, b"\x10"), "one|");
+        assert_eq!(edit(&mut editor, b"\x1b[A"), "one|");
+        assert_eq!(edit(&mut editor, b"\x1b[B"), "two|");
+        assert_eq!(edit(&mut editor, b"\x0e"), "dra|");
+        assert_eq!(edit(&mut editor, b"\x1b[B"), "dra|");
+    }
+
+    #[test]
+    fn history_keeps_the_newest_entries() {
+        let mut editor = new();
+        let entries: Vec<String> = (0..MAX_HISTORY + 2).map(|n| n.to_string()).collect();
+        editor.set_history(entries.clone());
+        assert_eq!(editor.history(), &entries[2..]);
+        feed(&mut editor, b"new\r");
+        assert_eq!(editor.history().len(), MAX_HISTORY);
+        assert_eq!(editor.history()[0], "3");
+        assert_eq!(editor.history()[MAX_HISTORY - 1], "new");
+    }
+
+    #[test]
+    fn tab_completes() {
+        let mut editor = new();
+        // One candidate: completed with a space, or without one for a directory.
+        assert_eq!(edit(&mut editor, b"hi\t"), "history |");
+        assert_eq!(edit(&mut editor, b"l\t"), "history logs/|");
+        // Several: up to what they have in common, then nothing more.
+        assert_eq!(edit(&mut editor, b"\x15h\t"), "h|");
+        assert_eq!(edit(&mut editor, b"e\t"), "he|");
+        assert_eq!(edit(&mut editor, b"x\t"), "hexdump |");
+        // Completes the word before the cursor, keeping the rest of the line.
+        assert_eq!(edit(&mut editor, b"\x01he\t"), "he|hexdump ");
+        assert_eq!(edit(&mut editor, b"\x15q\t"), "q|hexdump ");
+    }
+
+    #[test]
+    fn common_prefix_stops_at_a_char_boundary() {
+        // 'é' and 'è' share their first UTF-8 byte.
+        let names = Names(&["aé", "aè"]);
+        let mut editor = new();
+        let mut term = String::new();
+        for &byte in b"a\t" {
+            editor.feed(byte, &names, &mut term);
+        }
+        assert_eq!((editor.line.as_str(), editor.cursor), ("a", 1));
+        assert!(term.ends_with("\r\naé  aè\r\n\r> a\x1b[K"), "{:?}", term);
+    }
+
+    #[test]
+    fn ambiguous_completion_lists_the_candidates() {
+        let mut editor = new();
+        let mut term = String::new();
+        for &byte in b"he\t" {
+            editor.feed(byte, &NAMES, &mut term);
+        }
+        assert!(
+            term.ends_with("\r\nhelp  hexdump\r\n\r> he\x1b[K"),
+            "{:?}",
+            term
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! # shell::logs
+//! Log commands backed by [`crate::logger`]: `dmesg` shows the in-RAM log,
+//! `log` changes levels and file logging.
+//!
+
+use anyhow::{anyhow, bail, Result};
+use log::{Level, LevelFilter};
+
+use super::{ArgSpec, CommandRegistry, Context, FnCommand, Output};
+use crate::logger::{self, LOG_FILE};
+
+const LOG_USAGE: &str = "[level <target|*> <off|error|warn|info|debug|trace> | file <on|off>]";
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(FnCommand::new(
+        "dmesg",
+        "Show recent log records (-c clears them)",
+        ArgSpec::new("[-c] [error|warn|info|debug|trace]", 0, Some(2)),
+        dmesg,
+    ));
+    registry.register(FnCommand::new(
+        "log",
+        "Show or set log levels per target, or log to a file",
+        ArgSpec::new(LOG_USAGE, 0, Some(3)),
+        log_settings,
+    ));
+}
+
+/// `dmesg [-c] [level]`: records at `level` or more severe (default: all kept).
+fn dmesg(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let mut clear = false;
+    let mut min_level = Level::Trace;
+    for arg in args {
+        match *arg {
+            "-c" => clear = true,
+            level => {
+                min_level = level
+                    .parse()
+                    .map_err(|_| anyhow!("invalid level: {}", level))?
+            }
+        }
+    }
+
+    let logger = logger::logger();
+    for entry in logger.entries(min_level) {
+        out.write_line(&entry.format());
+    }
+    if clear {
+        logger.clear();
+    }
+    Ok(())
+}
+
+/// `log`: without arguments, shows the current settings.
+fn log_settings(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let logger = logger::logger();
+    match args {
+        [] => {
+            out.write_line(&format!("Max level: {}", log::max_level()));
+            for (target, level) in logger.levels() {
+                out.write_line(&format!("  {}: {}", target, level));
+            }
+            let file = if logger.file_logging() { "on" } else { "off" };
+            out.write_line(&format!("File ({}): {}", LOG_FILE, file));
+        }
+        ["level", target, level] => {
+            let level: LevelFilter = level
+                .parse()
+                .map_err(|_| anyhow!("invalid level: {}", level))?;
+            logger.set_level(target, level)?;
+            if level > log::max_level() {
+                out.write_line(&format!(
+                    "Note: this build logs at most {}",
+                    log::max_level()
+                ));
+            }
+        }
+        ["file", "on"] => logger
+            .set_file_logging(true)
+            .map_err(|e| anyhow!("cannot open {}: {}", LOG_FILE, e))?,
+        ["file", "off"] => logger.set_file_logging(false)?,
+        _ => bail!("usage: log {}", LOG_USAGE),
+    }
+    Ok(())
+}
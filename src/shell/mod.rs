@@ -0,0 +1,191 @@
+//! # shell
+//! Command shell shared by the shell front-ends (see `rtos_shell_app`).
+//!
+//! Commands implement [`ShellCommand`] and are collected in a [`CommandRegistry`];
+//! a [`Shell`] parses input lines (see [`parser`]) and dispatches them, writing to an [`Output`].
+//!
+//! The commands that call into ESP-IDF (`hw`, `logs`, `nvs`, `sys`) are only built
+//! for the chip; the rest also builds on the host, where `host-tests` tests it.
+//!
+
+pub mod builtins;
+pub mod command;
+pub mod fs;
+#[cfg(target_os = "espidf")]
+pub mod hw;
+pub mod line_editor;
+#[cfg(target_os = "espidf")]
+pub mod logs;
+#[cfg(target_os = "espidf")]
+pub mod nvs;
+pub mod output;
+pub mod parser;
+pub mod script;
+#[cfg(target_os = "espidf")]
+pub mod sys;
+pub mod telnet;
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use line_editor::{Completer, Completion};
+
+pub use command::{
+    ArgSpec, CommandFn, CommandRegistry, Context, Env, ExitStatus, FnCommand, ShellCommand,
+};
+pub use output::{ChannelOutput, ConsoleOutput, Output, OutputEvent, Scroll, TeeOutput};
+
+/// Builds a registry with the built-in, file, system, hardware, script and log commands.
+pub fn default_registry() -> CommandRegistry {
+    let mut registry = CommandRegistry::new();
+    builtins::register(&mut registry);
+    fs::register(&mut registry);
+    script::register(&mut registry);
+    #[cfg(target_os = "espidf")]
+    {
+        sys::register(&mut registry);
+        hw::register(&mut registry);
+        logs::register(&mut registry);
+    }
+    registry
+}
+
+/// Executes input lines against a [`CommandRegistry`], keeping the shell variables.
+pub struct Shell {
+    registry: Arc<CommandRegistry>,
+    env: Env,
+    status: i32,
+    exit: Option<i32>,
+}
+
+impl Shell {
+    pub fn new(registry: Arc<CommandRegistry>) -> Self {
+        Self {
+            registry,
+            env: Env::new(),
+            status: 0,
+            exit: None,
+        }
+    }
+
+    /// Runs every command on `line`, reporting errors to `out`.
+    ///
+    /// Returns the exit status of the last command (0 on success, 1 on error,
+    /// 2 if the line could not be parsed), also available as `$?`.
+    pub fn execute(&mut self, line: &str, out: &mut dyn Output) -> i32 {
+        self.with_context(|ctx| execute(ctx, line, out))
+    }
+
+    /// Runs a script file (see [`script`]) and returns its exit status.
+    pub fn run_script(&mut self, path: &str, out: &mut dyn Output) -> i32 {
+        self.with_context(|ctx| {
+            if let Err(e) = script::run_file(ctx, path, out) {
+                out.write_line(&e.to_string());
+                ctx.status = 1;
+            }
+        })
+    }
+
+    /// The status passed to `exit` since the last call, if any. Front-ends that
+    /// can end a session (e.g. a remote connection) close it when this is set.
+    pub fn take_exit(&mut self) -> Option<i32> {
+        self.exit.take()
+    }
+
+    fn with_context(&mut self, f: impl FnOnce(&mut Context)) -> i32 {
+        let mut ctx = Context {
+            registry: &self.registry,
+            env: &mut self.env,
+            status: self.status,
+            exit: None,
+            depth: 0,
+        };
+        f(&mut ctx);
+        self.status = ctx.status;
+        self.exit = ctx.exit;
+        self.status
+    }
+}
+
+/// Runs every command on `line` in `ctx`, reporting errors to `out` and leaving the
+/// exit status in `ctx.status`. Stops early when a command calls `exit`.
+pub fn execute(ctx: &mut Context, line: &str, out: &mut dyn Output) {
+    let commands = {
+        let env = &ctx.env;
+        let status = ctx.status;
+        let lookup = |name: &str| match name {
+            "?" => Some(status.to_string()),
+            name => env.get(name).cloned(),
+        };
+        parser::parse(line, &lookup)
+    };
+
+    match commands {
+        Ok(commands) => {
+            for words in commands {
+                let args: Vec<&str> = words[1..].iter().map(String::as_str).collect();
+                ctx.status = match run_command(ctx, &words[0], &args, out) {
+                    Ok(()) => 0,
+                    Err(e) => match e.downcast_ref::<ExitStatus>() {
+                        Some(ExitStatus(status)) => *status,
+                        None => {
+                            out.write_line(&e.to_string());
+                            1
+                        }
+                    },
+                };
+                if let Some(status) = ctx.exit {
+                    ctx.status = status;
+                    break;
+                }
+            }
+        }
+        Err(e) => {
+            out.write_line(&format!("parse error: {}", e));
+            ctx.status = 2;
+        }
+    }
+}
+
+/// Looks up, checks and runs a single command. Errors are prefixed with the command name.
+fn run_command(ctx: &mut Context, name: &str, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let registry = ctx.registry;
+    let command = registry
+        .get(name)
+        .ok_or_else(|| anyhow!("Unknown: {}", name))?;
+    command
+        .args()
+        .check(name, args)
+        .and_then(|()| command.run(ctx, args, out))
+        .map_err(|e| {
+            if e.is::<ExitStatus>() {
+                e
+            } else {
+                anyhow!("{}: {}", name, e)
+            }
+        })
+}
+
+impl Completer for Shell {
+    /// Completes command names in command position and `/spiffs` paths elsewhere.
+    fn complete(&self, line: &str) -> Completion {
+        let start = line
+            .rfind(|c: char| c.is_ascii_whitespace() || c == ';')
+            .map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let before = line[..start].trim_end();
+
+        let candidates = if before.is_empty() || before.ends_with(';') {
+            self.registry
+                .iter()
+                .map(|command| command.name())
+                .filter(|name| name.starts_with(word))
+                .map(str::to_string)
+                .collect()
+        } else {
+            fs::complete_path(word)
+        };
+        Completion { start, candidates }
+    }
+}
@@ -0,0 +1,337 @@
+//! # shell::nvs
+//! The `nvs` command: browse and edit key/value pairs in the default NVS partition
+//! (boot app selection, Wi-Fi credentials, settings, ...).
+//!
+//! Values are read and written through [`EspNvs`]; listing keys and partition
+//! statistics use the raw iterator API, which `EspNvs` doesn't wrap.
+//!
+//! Output may be mirrored to the display or a remote session, so the values of
+//! known secrets (the Wi-Fi and telnet passwords) are masked unless asked for
+//! with `nvs get -s`.
+//!
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use anyhow::{anyhow, bail, Result};
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_sys::{self as sys, esp};
+
+use super::{telnet, ArgSpec, Context, Output, ShellCommand};
+use crate::wifi;
+
+/// Label of the partition behind [`EspDefaultNvsPartition`].
+const PARTITION_C: &CStr = c"nvs";
+
+const USAGE: &str =
+    "list [ns] | get [-s] <ns> <key> | set <type> <ns> <key> <value...> | erase <ns> [key] | stats";
+
+/// `(namespace, key)` of values that are masked in output.
+const SECRETS: &[(&str, &str)] = &[
+    (wifi::NVS_NAMESPACE, wifi::NVS_PASS_KEY),
+    (telnet::NVS_NAMESPACE, telnet::NVS_PASSWORD_KEY),
+];
+const MASKED: &str = "******** (secret, 'nvs get -s' shows it)";
+
+fn is_secret(namespace: &str, key: &str) -> bool {
+    SECRETS.contains(&(namespace, key))
+}
+
+/// Value types NVS stores, named as in `nvs set <type>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    Str,
+    Blob,
+}
+
+impl ValueType {
+    const NAMES: &'static str = "u8|i8|u16|i16|u32|i32|u64|i64|str|blob";
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "u8" => Some(Self::U8),
+            "i8" => Some(Self::I8),
+            "u16" => Some(Self::U16),
+            "i16" => Some(Self::I16),
+            "u32" => Some(Self::U32),
+            "i32" => Some(Self::I32),
+            "u64" => Some(Self::U64),
+            "i64" => Some(Self::I64),
+            "str" => Some(Self::Str),
+            "blob" => Some(Self::Blob),
+            _ => None,
+        }
+    }
+
+    fn from_sys(ty: sys::nvs_type_t) -> Option<Self> {
+        match ty {
+            sys::nvs_type_t_NVS_TYPE_U8 => Some(Self::U8),
+            sys::nvs_type_t_NVS_TYPE_I8 => Some(Self::I8),
+            sys::nvs_type_t_NVS_TYPE_U16 => Some(Self::U16),
+            sys::nvs_type_t_NVS_TYPE_I16 => Some(Self::I16),
+            sys::nvs_type_t_NVS_TYPE_U32 => Some(Self::U32),
+            sys::nvs_type_t_NVS_TYPE_I32 => Some(Self::I32),
+            sys::nvs_type_t_NVS_TYPE_U64 => Some(Self::U64),
+            sys::nvs_type_t_NVS_TYPE_I64 => Some(Self::I64),
+            sys::nvs_type_t_NVS_TYPE_STR => Some(Self::Str),
+            sys::nvs_type_t_NVS_TYPE_BLOB => Some(Self::Blob),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::U8 => "u8",
+            Self::I8 => "i8",
+            Self::U16 => "u16",
+            Self::I16 => "i16",
+            Self::U32 => "u32",
+            Self::I32 => "i32",
+            Self::U64 => "u64",
+            Self::I64 => "i64",
+            Self::Str => "str",
+            Self::Blob => "blob",
+        }
+    }
+}
+
+/// One key found by [`entries`].
+struct Entry {
+    namespace: String,
+    key: String,
+    ty: Option<ValueType>,
+}
+
+/// Lists the keys of `namespace`, or of every namespace.
+fn entries(namespace: Option<&str>) -> Result<Vec<Entry>> {
+    let namespace = namespace.map(CString::new).transpose()?;
+    let namespace_ptr = namespace.as_ref().map_or(ptr::null(), |ns| ns.as_ptr());
+
+    let mut entries = Vec::new();
+    let mut iterator: sys::nvs_iterator_t = ptr::null_mut();
+    let mut err = unsafe {
+        sys::nvs_entry_find(
+            PARTITION_C.as_ptr(),
+            namespace_ptr,
+            sys::nvs_type_t_NVS_TYPE_ANY,
+            &mut iterator,
+        )
+    };
+    while err == sys::ESP_OK as sys::esp_err_t {
+        let mut info = sys::nvs_entry_info_t::default();
+        esp!(unsafe { sys::nvs_entry_info(iterator, &mut info) })?;
+        entries.push(Entry {
+            namespace: c_chars(&info.namespace_name),
+            key: c_chars(&info.key),
+            ty: ValueType::from_sys(info.type_),
+        });
+        err = unsafe { sys::nvs_entry_next(&mut iterator) };
+    }
+    unsafe { sys::nvs_release_iterator(iterator) };
+
+    // The iterator ends with "not found"; anything else is a real error.
+    if err != sys::ESP_ERR_NVS_NOT_FOUND as sys::esp_err_t {
+        esp!(err)?;
+    }
+    Ok(entries)
+}
+
+fn c_chars(chars: &[core::ffi::c_char]) -> String {
+    unsafe { CStr::from_ptr(chars.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        bail!("blob must be an even number of hex digits");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("invalid hex: {}", hex))
+        })
+        .collect()
+}
+
+fn read_value(nvs: &EspNvs<NvsDefault>, key: &str, ty: ValueType) -> Result<Option<String>> {
+    let value = match ty {
+        ValueType::U8 => nvs.get_u8(key)?.map(|v| v.to_string()),
+        ValueType::I8 => nvs.get_i8(key)?.map(|v| v.to_string()),
+        ValueType::U16 => nvs.get_u16(key)?.map(|v| v.to_string()),
+        ValueType::I16 => nvs.get_i16(key)?.map(|v| v.to_string()),
+        ValueType::U32 => nvs.get_u32(key)?.map(|v| v.to_string()),
+        ValueType::I32 => nvs.get_i32(key)?.map(|v| v.to_string()),
+        ValueType::U64 => nvs.get_u64(key)?.map(|v| v.to_string()),
+        ValueType::I64 => nvs.get_i64(key)?.map(|v| v.to_string()),
+        ValueType::Str => match nvs.str_len(key)? {
+            Some(len) => {
+                let mut buf = vec![0u8; len];
+                nvs.get_str(key, &mut buf)?.map(str::to_string)
+            }
+            None => None,
+        },
+        ValueType::Blob => match nvs.blob_len(key)? {
+            Some(len) => {
+                let mut buf = vec![0u8; len];
+                nvs.get_blob(key, &mut buf)?.map(to_hex)
+            }
+            None => None,
+        },
+    };
+    Ok(value)
+}
+
+fn write_value(nvs: &mut EspNvs<NvsDefault>, key: &str, ty: ValueType, value: &str) -> Result<()> {
+    let invalid = || anyhow!("invalid {} value: {}", ty.name(), value);
+    match ty {
+        ValueType::U8 => nvs.set_u8(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::I8 => nvs.set_i8(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::U16 => nvs.set_u16(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::I16 => nvs.set_i16(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::U32 => nvs.set_u32(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::I32 => nvs.set_i32(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::U64 => nvs.set_u64(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::I64 => nvs.set_i64(key, value.parse().map_err(|_| invalid())?)?,
+        ValueType::Str => nvs.set_str(key, value)?,
+        ValueType::Blob => nvs.set_blob(key, &from_hex(value)?)?,
+    }
+    Ok(())
+}
+
+/// `nvs`: inspects and edits the default NVS partition. Registered by apps that
+/// hold the partition (see `rtos_shell_app`).
+pub struct NvsCommand {
+    partition: EspDefaultNvsPartition,
+}
+
+impl NvsCommand {
+    pub fn new(partition: EspDefaultNvsPartition) -> Self {
+        Self { partition }
+    }
+
+    fn open(&self, namespace: &str, read_write: bool) -> Result<EspNvs<NvsDefault>> {
+        EspNvs::new(self.partition.clone(), namespace, read_write)
+            .map_err(|e| anyhow!("cannot open namespace {}: {}", namespace, e))
+    }
+
+    fn list(&self, namespace: Option<&str>, out: &mut dyn Output) -> Result<()> {
+        let entries = entries(namespace)?;
+        if entries.is_empty() {
+            out.write_line("(no keys)");
+        }
+        for entry in entries {
+            let value = match entry.ty {
+                Some(ty) if is_secret(&entry.namespace, &entry.key) => {
+                    format!("{} {}", ty.name(), MASKED)
+                }
+                Some(ty) => {
+                    let nvs = self.open(&entry.namespace, false)?;
+                    let value = read_value(&nvs, &entry.key, ty)?.unwrap_or_default();
+                    format!("{} {}", ty.name(), value)
+                }
+                None => "?".to_string(),
+            };
+            out.write_line(&format!("{}/{}: {}", entry.namespace, entry.key, value));
+        }
+        Ok(())
+    }
+
+    /// Prints one value; secrets only with `show_secret`.
+    fn get(
+        &self,
+        namespace: &str,
+        key: &str,
+        show_secret: bool,
+        out: &mut dyn Output,
+    ) -> Result<()> {
+        let ty = entries(Some(namespace))?
+            .into_iter()
+            .find(|entry| entry.key == key)
+            .and_then(|entry| entry.ty)
+            .ok_or_else(|| anyhow!("no such key: {}/{}", namespace, key))?;
+        if is_secret(namespace, key) && !show_secret {
+            out.write_line(&format!("{} {}", ty.name(), MASKED));
+            return Ok(());
+        }
+        let nvs = self.open(namespace, false)?;
+        let value = read_value(&nvs, key, ty)?.unwrap_or_default();
+        out.write_line(&format!("{} {}", ty.name(), value));
+        Ok(())
+    }
+
+    fn erase(&self, namespace: &str, key: Option<&str>, out: &mut dyn Output) -> Result<()> {
+        let mut nvs = self.open(namespace, true)?;
+        match key {
+            Some(key) => {
+                if !nvs.remove(key)? {
+                    bail!("no such key: {}/{}", namespace, key);
+                }
+            }
+            None => {
+                let entries = entries(Some(namespace))?;
+                for entry in &entries {
+                    nvs.remove(&entry.key)?;
+                }
+                out.write_line(&format!("Erased {} key(s) in {}", entries.len(), namespace));
+            }
+        }
+        Ok(())
+    }
+
+    fn stats(&self, out: &mut dyn Output) -> Result<()> {
+        let mut stats = sys::nvs_stats_t::default();
+        esp!(unsafe { sys::nvs_get_stats(PARTITION_C.as_ptr(), &mut stats) })?;
+        out.write_line(&format!(
+            "Entries: {} used, {} free, {} total",
+            stats.used_entries, stats.free_entries, stats.total_entries
+        ));
+        out.write_line(&format!("Namespaces: {}", stats.namespace_count));
+        Ok(())
+    }
+}
+
+impl ShellCommand for NvsCommand {
+    fn name(&self) -> &str {
+        "nvs"
+    }
+
+    fn help(&self) -> &str {
+        "List, read, write and erase NVS keys"
+    }
+
+    fn args(&self) -> ArgSpec {
+        ArgSpec::new(USAGE, 1, None)
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+        match (args[0], &args[1..]) {
+            ("list", []) => self.list(None, out),
+            ("list", [namespace]) => self.list(Some(*namespace), out),
+            ("get", [namespace, key]) => self.get(namespace, key, false, out),
+            ("get", ["-s", namespace, key]) => self.get(namespace, key, true, out),
+            ("set", [ty, namespace, key, value @ ..]) if !value.is_empty() => {
+                let ty = ValueType::parse(ty)
+                    .ok_or_else(|| anyhow!("invalid type: {} ({})", ty, ValueType::NAMES))?;
+                let mut nvs = self.open(namespace, true)?;
+                write_value(&mut nvs, key, ty, &value.join(" "))
+            }
+            ("erase", [namespace]) => self.erase(namespace, None, out),
+            ("erase", [namespace, key]) => self.erase(namespace, Some(*key), out),
+            ("stats", []) => self.stats(out),
+            _ => bail!("usage: nvs {}", USAGE),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! # shell::output
+//! Sinks that command output is written to.
+//!
+
+use std::io::{stdout, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Where command output goes. Front-ends implement this for their display/serial sinks.
+pub trait Output {
+    /// Writes one line of output.
+    fn write_line(&mut self, line: &str);
+
+    /// Clears whatever the sink shows; no-op for sinks that can't.
+    fn clear(&mut self) {}
+}
+
+/// Output as sent through a [`ChannelOutput`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputEvent {
+    Line(String),
+    Clear,
+    /// Pages the receiver's scrollback, in order with the lines around it.
+    Scroll(Scroll),
+}
+
+/// Scrollback request carried by [`OutputEvent::Scroll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scroll {
+    Up(usize),
+    Down(usize),
+    Top,
+    Bottom,
+}
+
+/// Forwards output to another thread, e.g. the display loop.
+///
+/// Writing never blocks on the receiver, so commands can stream long output
+/// without holding any lock the receiver needs.
+#[derive(Clone)]
+pub struct ChannelOutput {
+    tx: Sender<OutputEvent>,
+}
+
+impl ChannelOutput {
+    /// Creates a sink and the receiver its events arrive on.
+    pub fn channel() -> (Self, Receiver<OutputEvent>) {
+        let (tx, rx) = mpsc::channel();
+        (Self { tx }, rx)
+    }
+
+    /// Sends an event that isn't written through [`Output`], e.g. a [`Scroll`].
+    pub fn send(&self, event: OutputEvent) {
+        // A closed receiver just means nobody is watching any more.
+        let _ = self.tx.send(event);
+    }
+}
+
+impl Output for ChannelOutput {
+    fn write_line(&mut self, line: &str) {
+        self.send(OutputEvent::Line(line.to_string()));
+    }
+
+    fn clear(&mut self) {
+        self.send(OutputEvent::Clear);
+    }
+}
+
+/// Writes output to the serial console (stdout) using VT100 line endings.
+pub struct ConsoleOutput;
+
+impl Output for ConsoleOutput {
+    fn write_line(&mut self, line: &str) {
+        let mut out = stdout().lock();
+        let _ = write!(out, "{}\r\n", line);
+        let _ = out.flush();
+    }
+
+    fn clear(&mut self) {
+        let mut out = stdout().lock();
+        let _ = out.write_all(b"\x1b[2J\x1b[H");
+        let _ = out.flush();
+    }
+}
+
+/// Duplicates output to two sinks, e.g. the serial console and the display.
+pub struct TeeOutput<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A: Output, B: Output> TeeOutput<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<A: Output, B: Output> Output for TeeOutput<A, B> {
+    fn write_line(&mut self, line: &str) {
+        self.first.write_line(line);
+        self.second.write_line(line);
+    }
+
+    fn clear(&mut self) {
+        self.first.clear();
+        self.second.clear();
+    }
+}
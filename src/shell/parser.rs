@@ -0,0 +1,274 @@
+//! # shell::parser
+//! Splits an input line into commands and words.
+//!
+//! Supported syntax:
+//! - `;` separates commands on one line,
+//! - `'single quotes'` keep their content literally,
+//! - `"double quotes"` keep spaces but still expand variables and `\"`, `\\`, `\$` escapes,
+//! - `\x` outside quotes makes `x` literal,
+//! - `$NAME` and `${NAME}` expand to the variable's value (empty if unset).
+//!
+//! The parser has no ESP-IDF dependencies so it can be exercised on the host.
+//!
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Why a line could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedQuote(char),
+    UnterminatedBrace,
+    TrailingBackslash,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(q) => write!(f, "missing closing {}", q),
+            ParseError::UnterminatedBrace => write!(f, "missing closing }}"),
+            ParseError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses `line` into a list of commands, each a non-empty list of words.
+/// `lookup` resolves variable names during expansion.
+pub fn parse(
+    line: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<Vec<Vec<String>>, ParseError> {
+    let mut commands = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    // Distinguishes an empty quoted word (`""`) from no word at all.
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => end_word(&mut words, &mut word, &mut in_word),
+            ';' => {
+                end_word(&mut words, &mut word, &mut in_word);
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            '\\' => {
+                word.push(chars.next().ok_or(ParseError::TrailingBackslash)?);
+                in_word = true;
+            }
+            '\'' => {
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('\'')),
+                    }
+                }
+                in_word = true;
+            }
+            '"' => {
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(ParseError::UnterminatedQuote('"')),
+                        },
+                        Some('$') => expand(&mut chars, &mut word, lookup)?,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('"')),
+                    }
+                }
+                in_word = true;
+            }
+            '$' => {
+                // An unquoted expansion to nothing doesn't create a word (`echo $UNSET`).
+                expand(&mut chars, &mut word, lookup)?;
+                in_word |= !word.is_empty();
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    end_word(&mut words, &mut word, &mut in_word);
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    Ok(commands)
+}
+
+/// Returns `true` if `name` is usable as a variable name (`[A-Za-z_][A-Za-z0-9_]*`).
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn end_word(words: &mut Vec<String>, word: &mut String, in_word: &mut bool) {
+    if *in_word {
+        words.push(std::mem::take(word));
+        *in_word = false;
+    }
+}
+
+/// Expands the variable reference following a `$`. A `$` not followed by a
+/// name (or `?`) is kept literally.
+fn expand(
+    chars: &mut Peekable<Chars>,
+    word: &mut String,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), ParseError> {
+    let mut name = String::new();
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(ParseError::UnterminatedBrace),
+                }
+            }
+        }
+        Some('?') => {
+            chars.next();
+            name.push('?');
+        }
+        _ => {
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+    }
+
+    if name.is_empty() {
+        word.push('$');
+    } else if let Some(value) = lookup(&name) {
+        word.push_str(&value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "NAME" => Some("esp32".to_string()),
+            "SPACED" => Some("a b".to_string()),
+            "?" => Some("1".to_string()),
+            _ => None,
+        }
+    }
+
+    fn words(line: &str) -> Vec<Vec<String>> {
+        parse(line, &lookup).unwrap()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(
+            words("  echo   hello\tworld "),
+            [["echo", "hello", "world"]]
+        );
+        assert_eq!(words("   "), Vec::<Vec<String>>::new());
+    }
+
+    #[test]
+    fn semicolons_separate_commands() {
+        assert_eq!(
+            words("echo a; echo b;echo c"),
+            [["echo", "a"], ["echo", "b"], ["echo", "c"]]
+        );
+        assert_eq!(words(";; echo a ;"), [["echo", "a"]]);
+        assert_eq!(words("echo 'a;b'"), [["echo", "a;b"]]);
+    }
+
+    #[test]
+    fn quotes_keep_spaces_and_join_words() {
+        assert_eq!(words("echo 'a  b' \"c d\""), [["echo", "a  b", "c d"]]);
+        assert_eq!(words("echo x'y'\"z\""), [["echo", "xyz"]]);
+        assert_eq!(words(r"echo '\$NAME'"), [["echo", r"\$NAME"]]);
+    }
+
+    #[test]
+    fn empty_quotes_are_a_word() {
+        assert_eq!(words("set X \"\""), [["set", "X", ""]]);
+        assert_eq!(words("echo '' end"), [["echo", "", "end"]]);
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert_eq!(
+            words(r"echo a\ b \$NAME \;"),
+            [["echo", "a b", "$NAME", ";"]]
+        );
+        assert_eq!(
+            words(r#"echo "\" \\ \$NAME \n""#),
+            [["echo", r#"" \ $NAME \n"#]]
+        );
+    }
+
+    #[test]
+    fn variables_expand() {
+        assert_eq!(words("echo $NAME ${NAME}!"), [["echo", "esp32", "esp32!"]]);
+        assert_eq!(words("echo $?"), [["echo", "1"]]);
+        assert_eq!(words("echo \"$NAME-${NAME}\""), [["echo", "esp32-esp32"]]);
+        // Expanded text is not split again.
+        assert_eq!(words("echo $SPACED"), [["echo", "a b"]]);
+    }
+
+    #[test]
+    fn unset_variables_expand_to_nothing() {
+        assert_eq!(words("echo $UNSET end"), [["echo", "end"]]);
+        assert_eq!(words("echo \"$UNSET\" end"), [["echo", "", "end"]]);
+        assert_eq!(words("echo a${UNSET}b"), [["echo", "ab"]]);
+    }
+
+    #[test]
+    fn lone_dollar_is_literal() {
+        assert_eq!(words("echo $ a$ \"$\""), [["echo", "$", "a$", "$"]]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |line| parse(line, &lookup).unwrap_err();
+        assert_eq!(error("echo 'abc"), ParseError::UnterminatedQuote('\''));
+        assert_eq!(error("echo \"abc"), ParseError::UnterminatedQuote('"'));
+        assert_eq!(error(r#"echo "abc\"#), ParseError::UnterminatedQuote('"'));
+        assert_eq!(error("echo ${NAME"), ParseError::UnterminatedBrace);
+        assert_eq!(error("echo \"${NAME\""), ParseError::UnterminatedBrace);
+        assert_eq!(error(r"echo abc\"), ParseError::TrailingBackslash);
+        assert_eq!(error("echo 'abc").to_string(), "missing closing '");
+        assert_eq!(error("echo ${NAME").to_string(), "missing closing }");
+        assert_eq!(error(r"echo \").to_string(), "trailing backslash");
+    }
+
+    #[test]
+    fn variable_names() {
+        assert!(is_valid_name("PATH_2"));
+        assert!(is_valid_name("_x"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("2X"));
+        assert!(!is_valid_name("A-B"));
+    }
+}
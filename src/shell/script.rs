@@ -0,0 +1,291 @@
+//! # shell::script
+//! Shell scripts: `run <file>` executes a file line by line in the current shell.
+//!
+//! Scripts use the normal command syntax plus `#` comment lines and `if` blocks.
+//! `if` runs the rest of its line and takes the branch if it exits with 0
+//! (`if !` negates); `exit` stops the script with a status:
+//!
+//! ```text
+//! # bench or production?
+//! if test $MODE = prod
+//!   echo production
+//! else
+//!   echo bench
+//! end
+//! if ! test -e calibration.txt
+//!   exit 3
+//! end
+//! ```
+//!
+//! A failing command does not stop the script; the script's status is that of
+//! its last command, or the one given to `exit`.
+//!
+
+use std::fs;
+
+use anyhow::{anyhow, bail, Result};
+
+use super::{ArgSpec, CommandRegistry, Context, ExitStatus, FnCommand, Output};
+
+/// Script run by shell front-ends at startup, if it exists.
+pub const AUTORUN_FILE: &str = "/spiffs/autorun.sh";
+
+/// Limit for scripts running scripts, so a script that runs itself fails cleanly.
+const MAX_DEPTH: usize = 8;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(FnCommand::new(
+        "run",
+        "Run a script file line by line",
+        ArgSpec::new("<file>", 1, Some(1)),
+        run,
+    ));
+}
+
+/// `run <file>`: the command's status is the script's status.
+fn run(ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    run_file(ctx, args[0], out)?;
+    match ctx.status {
+        0 => Ok(()),
+        status => Err(ExitStatus(status).into()),
+    }
+}
+
+/// Runs the script at `path` (relative to `/spiffs`), leaving its status in `ctx.status`.
+/// `exit` inside the script ends the script only.
+pub fn run_file(ctx: &mut Context, path: &str, out: &mut dyn Output) -> Result<()> {
+    if ctx.depth >= MAX_DEPTH {
+        bail!("scripts nested more than {} deep", MAX_DEPTH);
+    }
+    let path = super::fs::resolve(path)?;
+    let text = fs::read_to_string(&path).map_err(|e| anyhow!("{}: {}", path, e))?;
+
+    ctx.depth += 1;
+    let result = run_text(ctx, &text, out);
+    ctx.depth -= 1;
+    if let Some(status) = ctx.exit.take() {
+        ctx.status = status;
+    }
+    result.map_err(|e| anyhow!("{}: {}", path, e))
+}
+
+/// An open `if` block.
+struct Block {
+    /// Line of the `if`, for a missing `end`.
+    line: usize,
+    /// Whether lines in the current branch run.
+    active: bool,
+    /// Whether the enclosing block is active at all.
+    outer_active: bool,
+    /// Whether the `if` branch was taken, so `else` is skipped.
+    taken: bool,
+}
+
+/// Runs script text. Fails only on malformed `if`/`else`/`end` structure.
+fn run_text(ctx: &mut Context, text: &str, out: &mut dyn Output) -> Result<()> {
+    let mut blocks: Vec<Block> = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let active = blocks.last().map_or(true, |block| block.active);
+        let (keyword, rest) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(keyword, rest)| (keyword, rest.trim_start()));
+
+        match keyword {
+            "if" => {
+                if rest.is_empty() {
+                    bail!("line {}: 'if' needs a command", number + 1);
+                }
+                let taken = active && {
+                    let (negate, condition) = match rest.strip_prefix('!') {
+                        Some(condition) => (true, condition),
+                        None => (false, rest),
+                    };
+                    super::execute(ctx, condition, out);
+                    (ctx.status == 0) != negate
+                };
+                blocks.push(Block {
+                    line: number + 1,
+                    active: taken,
+                    outer_active: active,
+                    taken,
+                });
+            }
+            "else" | "end" if !rest.is_empty() => {
+                bail!("line {}: '{}' takes nothing after it", number + 1, keyword);
+            }
+            "else" => {
+                let block = blocks
+                    .last_mut()
+                    .ok_or_else(|| anyhow!("line {}: 'else' without 'if'", number + 1))?;
+                block.active = block.outer_active && !block.taken;
+                block.taken = true;
+            }
+            "end" => {
+                blocks
+                    .pop()
+                    .ok_or_else(|| anyhow!("line {}: 'end' without 'if'", number + 1))?;
+            }
+            _ if active => super::execute(ctx, line, out),
+            _ => {}
+        }
+
+        if ctx.exit.is_some() {
+            return Ok(());
+        }
+    }
+
+    if let Some(block) = blocks.last() {
+        bail!("line {}: 'if' without 'end'", block.line);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{default_registry, Env};
+    use super::*;
+
+    /// Collects output lines.
+    struct Lines(Vec<String>);
+
+    impl Output for Lines {
+        fn write_line(&mut self, line: &str) {
+            self.0.push(line.to_string());
+        }
+    }
+
+    /// Runs `text` as a script and returns its result, status and output.
+    fn script(text: &str) -> (Result<()>, i32, Vec<String>) {
+        let registry = default_registry();
+        let mut env = Env::new();
+        let mut ctx = Context {
+            registry: &registry,
+            env: &mut env,
+            status: 0,
+            exit: None,
+            depth: 0,
+        };
+        let mut out = Lines(Vec::new());
+        let result = run_text(&mut ctx, text, &mut out);
+        (result, ctx.status, out.0)
+    }
+
+    fn output(text: &str) -> Vec<String> {
+        let (result, _, lines) = script(text);
+        result.unwrap();
+        lines
+    }
+
+    fn error(text: &str) -> String {
+        script(text).0.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn runs_lines_and_skips_comments() {
+        let (result, status, lines) = script("# comment\n\necho a; echo b\n  echo c\ntest 1 = 2\n");
+        result.unwrap();
+        assert_eq!(lines, ["a", "b", "c"]);
+        assert_eq!(status, 1, "the status of the last command");
+    }
+
+    #[test]
+    fn nested_if_else_end() {
+        let text = "
+if test a = a
+  echo outer
+  if test a = b
+    echo wrong
+  else
+    echo inner else
+    if test 1 -eq 1
+      echo deepest
+    end
+  end
+else
+  echo wrong
+  if test a = a
+    echo wrong
+  else
+    echo wrong
+  end
+end
+echo after
+";
+        assert_eq!(output(text), ["outer", "inner else", "deepest", "after"]);
+    }
+
+    #[test]
+    fn skipped_conditions_are_not_run() {
+        let text = "
+if test a = b
+  if echo ran
+  end
+end
+";
+        assert_eq!(output(text), Vec::<String>::new());
+    }
+
+    #[test]
+    fn if_not_negates() {
+        assert_eq!(
+            output("if !test a = b\necho negated\nend\nif ! test a = a\necho wrong\nend\n"),
+            ["negated"]
+        );
+    }
+
+    #[test]
+    fn exit_sets_the_status_and_stops() {
+        let (result, status, lines) =
+            script("echo before\nif test a = a\nexit 3\nend\necho after\n");
+        result.unwrap();
+        assert_eq!((status, lines), (3, vec!["before".to_string()]));
+    }
+
+    #[test]
+    fn scripts_nested_too_deep_are_rejected() {
+        let registry = default_registry();
+        let mut env = Env::new();
+        let mut ctx = Context {
+            registry: &registry,
+            env: &mut env,
+            status: 0,
+            exit: None,
+            depth: MAX_DEPTH,
+        };
+        let error = run_file(&mut ctx, "self.sh", &mut Lines(Vec::new())).unwrap_err();
+        assert_eq!(error.to_string(), "scripts nested more than 8 deep");
+        assert_eq!(ctx.depth, MAX_DEPTH);
+    }
+
+    #[test]
+    fn block_errors_give_the_line() {
+        assert_eq!(error("echo a\nelse\n"), "line 2: 'else' without 'if'");
+        assert_eq!(error("\n\nend\n"), "line 3: 'end' without 'if'");
+        assert_eq!(error("if\n"), "line 1: 'if' needs a command");
+        assert_eq!(
+            error("if test a = a\n  if test b = b\n  end\n"),
+            "line 1: 'if' without 'end'"
+        );
+    }
+
+    #[test]
+    fn else_and_end_take_nothing_after_them() {
+        assert_eq!(
+            error("if test a = a\nelse echo hi\nend\n"),
+            "line 2: 'else' takes nothing after it"
+        );
+        assert_eq!(
+            error("if test a = a\nend foo\n"),
+            "line 2: 'end' takes nothing after it"
+        );
+        assert_eq!(
+            output("if test a = a\n  else  \n end\n"),
+            Vec::<String>::new()
+        );
+    }
+}
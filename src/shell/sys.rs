@@ -0,0 +1,270 @@
+//! # shell::sys
+//! System introspection commands: `info`, `ps`, `top`, `heap`, `uptime`,
+//! `reset-reason`, `chipinfo` and `reboot`, plus [`LastCrashCommand`].
+//!
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+
+use super::{ArgSpec, CommandRegistry, Context, FnCommand, Output, ShellCommand};
+use crate::crash;
+use crate::storage;
+use crate::system::{self, TaskInfo};
+
+/// Sampling window of `top` when no duration is given.
+const TOP_DEFAULT_SECS: u64 = 1;
+const TOP_MAX_SECS: u64 = 60;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(FnCommand::new(
+        "info",
+        "Show IDF version, heap and filesystem usage",
+        ArgSpec::NONE,
+        info,
+    ));
+    registry.register(FnCommand::new(
+        "ps",
+        "List tasks with CPU share since boot (wraps at ~71 min)",
+        ArgSpec::NONE,
+        ps,
+    ));
+    registry.register(FnCommand::new(
+        "top",
+        "List tasks with CPU share over a sampling window",
+        ArgSpec::new("[seconds]", 0, Some(1)),
+        top,
+    ));
+    registry.register(FnCommand::new(
+        "heap",
+        "Show free/largest/min-ever heap per capability",
+        ArgSpec::NONE,
+        heap,
+    ));
+    registry.register(FnCommand::new(
+        "uptime",
+        "Show time since boot",
+        ArgSpec::NONE,
+        uptime,
+    ));
+    registry.register(FnCommand::new(
+        "reset-reason",
+        "Show why the chip last reset",
+        ArgSpec::NONE,
+        reset_reason,
+    ));
+    registry.register(FnCommand::new(
+        "chipinfo",
+        "Show chip model, revision, features, flash and MAC",
+        ArgSpec::NONE,
+        chipinfo,
+    ));
+    registry.register(FnCommand::new(
+        "reboot",
+        "Restart the chip",
+        ArgSpec::NONE,
+        reboot,
+    ));
+}
+
+fn info(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let idf = unsafe {
+        let vp = esp_idf_sys::esp_get_idf_version();
+        if vp.is_null() {
+            "unknown"
+        } else {
+            CStr::from_ptr(vp).to_str().unwrap_or("<??>")
+        }
+    };
+    out.write_line(&format!("IDF: {}", idf));
+    let free = unsafe { esp_idf_sys::esp_get_free_heap_size() };
+    out.write_line(&format!("Heap: {} bytes", free));
+    match storage::usage() {
+        Ok(u) => out.write_line(&format!("FS: {}/{} bytes used", u.used, u.total)),
+        Err(e) => out.write_line(&format!("FS: {}", e)),
+    }
+    Ok(())
+}
+
+/// Writes the task table, sorted by CPU share. `total` is the run time of all
+/// cores over the window, so the shares add up to 100%.
+fn write_tasks(out: &mut dyn Output, tasks: &[(TaskInfo, u64)], total: u64) {
+    let mut rows: Vec<_> = tasks.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1));
+
+    // Fits the 38 columns of the LCD.
+    out.write_line(&format!(
+        "{:<16} {:<5} {:>3} {:>5} {:>4}",
+        "NAME", "STATE", "PRI", "STACK", "CPU%"
+    ));
+    for (task, run_time) in rows {
+        let cpu = if total == 0 {
+            0
+        } else {
+            run_time * 100 / total
+        };
+        out.write_line(&format!(
+            "{:<16.16} {:<5} {:>3} {:>5} {:>4}",
+            task.name, task.state, task.priority, task.stack_high_water, cpu
+        ));
+    }
+}
+
+/// Run time of all cores; each task's counter only advances on its own core.
+fn all_cores(run_time: u64) -> u64 {
+    run_time * u64::from(system::chip_info().cores.max(1))
+}
+
+/// Ticks from `before` to `after` of a 32-bit run-time counter, across a wrap.
+fn elapsed(before: u64, after: u64) -> u64 {
+    u64::from((after as u32).wrapping_sub(before as u32))
+}
+
+/// `ps`: CPU shares are averaged since boot. The 32-bit run-time counters wrap
+/// after ~71 minutes at 1 MHz, after which the shares are meaningless; `top`
+/// measures a window and copes with the wrap.
+fn ps(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let snapshot = system::tasks();
+    let tasks: Vec<_> = snapshot
+        .tasks
+        .into_iter()
+        .map(|task| {
+            let run_time = task.run_time;
+            (task, run_time)
+        })
+        .collect();
+    write_tasks(out, &tasks, all_cores(snapshot.total_run_time));
+    Ok(())
+}
+
+/// `top [seconds]`: takes two snapshots and shows each task's share of the
+/// time between them. Tasks that ended in between are left out.
+fn top(_ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let secs = match args.first() {
+        Some(s) => s
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| (1..=TOP_MAX_SECS).contains(secs))
+            .ok_or_else(|| anyhow!("seconds must be 1..{}", TOP_MAX_SECS))?,
+        None => TOP_DEFAULT_SECS,
+    };
+
+    let before = system::tasks();
+    let start: BTreeMap<_, _> = before
+        .tasks
+        .iter()
+        .map(|task| (task.number, task.run_time))
+        .collect();
+    thread::sleep(Duration::from_secs(secs));
+    let after = system::tasks();
+
+    let tasks: Vec<_> = after
+        .tasks
+        .into_iter()
+        .map(|task| {
+            let since = start.get(&task.number).copied().unwrap_or(0);
+            let run_time = elapsed(since, task.run_time);
+            (task, run_time)
+        })
+        .collect();
+    write_tasks(
+        out,
+        &tasks,
+        all_cores(elapsed(before.total_run_time, after.total_run_time)),
+    );
+    Ok(())
+}
+
+/// `heap`: one row per `MALLOC_CAP_*` set, in bytes.
+fn heap(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    out.write_line(&format!(
+        "{:<6} {:>7} {:>7} {:>7} {:>7}",
+        "CAPS", "TOTAL", "FREE", "LARGEST", "MIN"
+    ));
+    for (name, caps) in system::HEAP_CAPS {
+        let stats = system::heap_stats(*caps);
+        if stats.total == 0 {
+            continue;
+        }
+        out.write_line(&format!(
+            "{:<6} {:>7} {:>7} {:>7} {:>7}",
+            name, stats.total, stats.free, stats.largest_block, stats.min_free_ever
+        ));
+    }
+    Ok(())
+}
+
+fn uptime(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    out.write_line(&format!("up {}", system::format_uptime(system::uptime())));
+    Ok(())
+}
+
+fn reset_reason(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    out.write_line(system::reset_reason());
+    Ok(())
+}
+
+fn chipinfo(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    let info = system::chip_info();
+    out.write_line(&format!(
+        "Chip: {} v{}.{}, {} core(s)",
+        info.model,
+        info.revision / 100,
+        info.revision % 100,
+        info.cores
+    ));
+    out.write_line(&format!("Features: {}", info.features.join(", ")));
+    out.write_line(&format!("Flash: {} KB", info.flash_size / 1024));
+    let mac: Vec<String> = info.mac.iter().map(|b| format!("{:02x}", b)).collect();
+    out.write_line(&format!("MAC: {}", mac.join(":")));
+    Ok(())
+}
+
+fn reboot(_ctx: &mut Context, _args: &[&str], out: &mut dyn Output) -> Result<()> {
+    out.write_line("Rebooting...");
+    // Give the console and display a moment to show the message.
+    thread::sleep(Duration::from_millis(100));
+    esp_idf_hal::reset::restart()
+}
+
+/// `lastcrash [-c]`: the report saved by [`crate::crash`] after the last panic or
+/// watchdog reset. Needs the NVS partition, so apps that hold it register it.
+pub struct LastCrashCommand {
+    partition: EspDefaultNvsPartition,
+}
+
+impl LastCrashCommand {
+    pub fn new(partition: EspDefaultNvsPartition) -> Self {
+        Self { partition }
+    }
+}
+
+impl ShellCommand for LastCrashCommand {
+    fn name(&self) -> &str {
+        "lastcrash"
+    }
+
+    fn help(&self) -> &str {
+        "Show the last crash report (-c clears it)"
+    }
+
+    fn args(&self) -> ArgSpec {
+        ArgSpec::new("[-c]", 0, Some(1))
+    }
+
+    fn run(&self, _ctx: &mut Context, args: &[&str], out: &mut dyn Output) -> Result<()> {
+        match args {
+            [] => match crash::load(&self.partition)? {
+                Some(lines) => lines.iter().for_each(|line| out.write_line(line)),
+                None => out.write_line("(no crash recorded)"),
+            },
+            ["-c"] => crash::clear(&self.partition)?,
+            _ => bail!("usage: lastcrash [-c]"),
+        }
+        Ok(())
+    }
+}
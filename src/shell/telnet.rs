@@ -0,0 +1,491 @@
+//! # shell::telnet
+//! Remote shell sessions over TCP, speaking enough of the telnet protocol for
+//! `telnet`, PuTTY or `nc` clients.
+//!
+//! Every session gets its own [`Shell`] (and variables) over the shared
+//! [`CommandRegistry`], plus a [`LineEditor`], and ends on `exit`, Ctrl-D,
+//! disconnect or a read timeout: [`LOGIN_TIMEOUT`] until the password is in, then
+//! [`IDLE_TIMEOUT`], so idle clients can't hold the session slots. Only `std::net`/`std::io` is used, so [`serve`] and [`session`]
+//! run unchanged on the host against a loopback socket.
+//!
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::{info, warn};
+
+use super::line_editor::LineEditor;
+use super::{CommandRegistry, Output, Shell};
+
+pub const DEFAULT_PORT: u16 = 23;
+
+/// Where apps keep the remote shell settings in NVS (Wi-Fi credentials: see `wifi`).
+pub const NVS_NAMESPACE: &str = "telnet";
+pub const NVS_PASSWORD_KEY: &str = "password";
+pub const NVS_PORT_KEY: &str = "port";
+
+/// Further connections are turned away while this many sessions are open.
+const MAX_SESSIONS: usize = 2;
+const MAX_LOGIN_ATTEMPTS: u32 = 3;
+/// Pause after a wrong password, to slow down guessing.
+const LOGIN_FAILURE_DELAY: Duration = Duration::from_secs(1);
+const SESSION_STACK_SIZE: usize = 8192;
+/// How long a client may take to send the password.
+#[cfg(not(test))]
+pub const LOGIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a logged-in session may go without input.
+#[cfg(not(test))]
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// Short enough for the loopback tests to wait out.
+#[cfg(test)]
+pub const LOGIN_TIMEOUT: Duration = Duration::from_millis(500);
+#[cfg(test)]
+pub const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+const PROMPT: &str = "esp> ";
+
+// Telnet commands and options (RFC 854, 857, 858).
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPT_ECHO: u8 = 1;
+const OPT_SUPPRESS_GO_AHEAD: u8 = 3;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+
+/// Accepts connections on `listener` forever, serving each in its own thread.
+/// With a `password`, clients must enter it before they get a prompt.
+pub fn serve(
+    listener: TcpListener,
+    registry: Arc<CommandRegistry>,
+    password: Option<String>,
+) -> Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Telnet accept failed: {}", e);
+                continue;
+            }
+        };
+        let peer = stream
+            .peer_addr()
+            .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+        if active.load(Ordering::SeqCst) >= MAX_SESSIONS {
+            info!("Telnet: refusing {}, too many sessions", peer);
+            let _ = stream.write_all(b"Too many sessions, try again later.\r\n");
+            continue;
+        }
+
+        if let Err(e) = stream.set_read_timeout(Some(LOGIN_TIMEOUT)) {
+            warn!("Telnet: refusing {}: {}", peer, e);
+            continue;
+        }
+
+        info!("Telnet: session from {}", peer);
+        active.fetch_add(1, Ordering::SeqCst);
+        let spawned = {
+            let registry = registry.clone();
+            let password = password.clone();
+            let active = active.clone();
+            thread::Builder::new()
+                .stack_size(SESSION_STACK_SIZE)
+                .spawn(move || {
+                    if let Err(e) = session(stream, registry, password.as_deref()) {
+                        info!("Telnet: session from {} failed: {}", peer, e);
+                    }
+                    info!("Telnet: session from {} closed", peer);
+                    active.fetch_sub(1, Ordering::SeqCst);
+                })
+        };
+        if let Err(e) = spawned {
+            warn!("Telnet: could not start session: {}", e);
+            active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+    Ok(())
+}
+
+/// Runs one session on `stream` until the client quits, disconnects or times out.
+pub fn session(
+    stream: TcpStream,
+    registry: Arc<CommandRegistry>,
+    password: Option<&str>,
+) -> io::Result<()> {
+    let mut conn = Connection::new(stream);
+    match run(&mut conn, registry, password) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            // The client may already be gone.
+            let _ = conn.stream.write_all(b"\r\nTimed out.\r\n");
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn run(
+    conn: &mut Connection<TcpStream>,
+    registry: Arc<CommandRegistry>,
+    password: Option<&str>,
+) -> io::Result<()> {
+    // The server echoes (through the line editor) and wants every key as it is typed.
+    conn.stream
+        .write_all(&[IAC, WILL, OPT_ECHO, IAC, WILL, OPT_SUPPRESS_GO_AHEAD])?;
+
+    if let Some(password) = password {
+        if !login(conn, password)? {
+            conn.stream.write_all(b"Login failed.\r\n")?;
+            return Ok(());
+        }
+    }
+    conn.stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+
+    let mut shell = Shell::new(registry);
+    let mut editor = LineEditor::new(PROMPT);
+    let mut term = String::new();
+    conn.stream
+        .write_all(b"Type 'help' for commands, 'exit' to quit.\r\n")?;
+    conn.stream.write_all(editor.prompt().as_bytes())?;
+
+    while let Some(byte) = conn.next_byte()? {
+        if byte == CTRL_D {
+            break;
+        }
+        term.clear();
+        let line = editor.feed(byte, &shell, &mut term);
+        conn.stream.write_all(term.as_bytes())?;
+        let Some(line) = line else {
+            continue;
+        };
+
+        let input = line.trim();
+        if !input.is_empty() {
+            let mut out = SessionOutput {
+                stream: &mut conn.stream,
+                error: None,
+            };
+            shell.execute(input, &mut out);
+            if let Some(e) = out.error {
+                return Err(e);
+            }
+            if shell.take_exit().is_some() {
+                break;
+            }
+        }
+        conn.stream.write_all(editor.prompt().as_bytes())?;
+    }
+
+    // The client may already be gone.
+    let _ = conn.stream.write_all(b"Bye.\r\n");
+    Ok(())
+}
+
+/// Asks for the password up to [`MAX_LOGIN_ATTEMPTS`] times.
+fn login<S: Read + Write>(conn: &mut Connection<S>, password: &str) -> io::Result<bool> {
+    for _ in 0..MAX_LOGIN_ATTEMPTS {
+        conn.stream.write_all(b"Password: ")?;
+        let Some(entered) = read_hidden_line(conn)? else {
+            return Ok(false);
+        };
+        conn.stream.write_all(b"\r\n")?;
+        if constant_time_eq(entered.as_bytes(), password.as_bytes()) {
+            return Ok(true);
+        }
+        thread::sleep(LOGIN_FAILURE_DELAY);
+        conn.stream.write_all(b"Wrong password.\r\n")?;
+    }
+    Ok(false)
+}
+
+/// Reads a line without echoing it. `None` if the client disconnects or
+/// presses Ctrl-C/Ctrl-D.
+fn read_hidden_line<S: Read + Write>(conn: &mut Connection<S>) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    while let Some(byte) = conn.next_byte()? {
+        match byte {
+            b'\r' | b'\n' => return Ok(Some(line)),
+            CTRL_C | CTRL_D => return Ok(None),
+            0x08 | 0x7f => {
+                line.pop();
+            }
+            byte if byte.is_ascii_graphic() || byte == b' ' => line.push(byte as char),
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Compares without stopping at the first difference, so timing doesn't reveal
+/// how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// A client connection: the stream plus the telnet input decoder. Also used by
+/// the MUD server.
+pub(crate) struct Connection<S> {
+    pub(crate) stream: S,
+    decoder: Decoder,
+    buf: [u8; 64],
+    pos: usize,
+    len: usize,
+}
+
+impl<S: Read + Write> Connection<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        Self {
+            stream,
+            decoder: Decoder::default(),
+            buf: [0; 64],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Next input byte with telnet commands removed; `None` once the client disconnects.
+    pub(crate) fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            if self.pos == self.len {
+                self.len = match self.stream.read(&mut self.buf) {
+                    Ok(0) => return Ok(None),
+                    Ok(len) => len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                self.pos = 0;
+            }
+            let byte = self.buf[self.pos];
+            self.pos += 1;
+            if let Some(byte) = self.decoder.feed(byte) {
+                return Ok(Some(byte));
+            }
+        }
+    }
+}
+
+/// Position in a telnet command sequence.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum DecoderState {
+    #[default]
+    Data,
+    /// Got IAC.
+    Command,
+    /// Got IAC WILL/WONT/DO/DONT, expecting the option byte.
+    Option,
+    /// Inside IAC SB ... IAC SE.
+    Subnegotiation,
+    /// Got IAC inside a subnegotiation.
+    SubnegotiationCommand,
+}
+
+/// Strips telnet commands from the input and folds `\r\n`/`\r\0` into `\r`.
+/// Option requests from the client are ignored; the server's own offers
+/// (echo, suppress go-ahead) are all a line-oriented shell needs.
+#[derive(Debug, Default)]
+struct Decoder {
+    state: DecoderState,
+    after_cr: bool,
+}
+
+impl Decoder {
+    fn feed(&mut self, byte: u8) -> Option<u8> {
+        match self.state {
+            DecoderState::Data => {
+                if byte == IAC {
+                    self.state = DecoderState::Command;
+                    return None;
+                }
+                let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+                if after_cr && (byte == b'\n' || byte == 0) {
+                    return None;
+                }
+                Some(byte)
+            }
+            DecoderState::Command => {
+                self.state = match byte {
+                    WILL | WONT | DO | DONT => DecoderState::Option,
+                    SB => DecoderState::Subnegotiation,
+                    _ => DecoderState::Data,
+                };
+                None
+            }
+            DecoderState::Option => {
+                self.state = DecoderState::Data;
+                None
+            }
+            DecoderState::Subnegotiation => {
+                if byte == IAC {
+                    self.state = DecoderState::SubnegotiationCommand;
+                }
+                None
+            }
+            DecoderState::SubnegotiationCommand => {
+                self.state = match byte {
+                    SE => DecoderState::Data,
+                    _ => DecoderState::Subnegotiation,
+                };
+                None
+            }
+        }
+    }
+}
+
+/// Sends command output to the client. The first write error is kept so the
+/// session can end after the command instead of failing every line.
+struct SessionOutput<'a, W: Write> {
+    stream: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Output for SessionOutput<'_, W> {
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(e) = write!(self.stream, "{}\r\n", line) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        if self.error.is_none() {
+            if let Err(e) = self.stream.write_all(b"\x1b[2J\x1b[H") {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{SocketAddr, TcpStream};
+
+    use super::super::default_registry;
+    use super::*;
+
+    /// Starts a server with password `secret` on a free loopback port.
+    fn start() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let registry = Arc::new(default_registry());
+        thread::spawn(move || serve(listener, registry, Some("secret".to_string())));
+        addr
+    }
+
+    /// Sends `input` all at once and returns everything the server says until it
+    /// hangs up.
+    fn transcript(addr: SocketAddr, input: &[u8]) -> String {
+        let output = transcript_raw(addr, input);
+        let negotiation = [IAC, WILL, OPT_ECHO, IAC, WILL, OPT_SUPPRESS_GO_AHEAD];
+        assert_eq!(output[..negotiation.len()], negotiation);
+        String::from_utf8(output[negotiation.len()..].to_vec()).unwrap()
+    }
+
+    /// Like [`transcript`], keeping the telnet negotiation.
+    fn transcript_raw(addr: SocketAddr, input: &[u8]) -> Vec<u8> {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(input).unwrap();
+        let mut output = Vec::new();
+        stream.read_to_end(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn logs_in_runs_commands_and_exits() {
+        let addr = start();
+        let mut input = b"wrong\r\nsecret\r\0".to_vec();
+        // Client negotiation, in the middle of a command and between two.
+        input.extend_from_slice(b"ec");
+        input.extend_from_slice(&[IAC, DO, OPT_ECHO]);
+        input.extend_from_slice(b"ho hello\r\n");
+        input.extend_from_slice(&[IAC, SB, 24, 1, IAC, SE]);
+        input.extend_from_slice(b"echo two\r\0exit\r\n");
+
+        let output = transcript(addr, &input);
+        assert!(
+            output.starts_with(
+                "Password: \r\nWrong password.\r\nPassword: \r\n\
+                 Type 'help' for commands, 'exit' to quit.\r\nesp> "
+            ),
+            "{:?}",
+            output
+        );
+        assert!(output.contains("\r\nhello\r\nesp> "), "{:?}", output);
+        assert!(output.contains("\r\ntwo\r\nesp> "), "{:?}", output);
+        // `\r\n` and `\r\0` end one line each, without an empty one after them.
+        let fresh_prompt = format!("\n{}", PROMPT);
+        assert_eq!(output.matches(&fresh_prompt).count(), 3, "{:?}", output);
+        assert!(output.ends_with("\r\nBye.\r\n"), "{:?}", output);
+    }
+
+    #[test]
+    fn silent_clients_time_out_and_free_their_slot() {
+        let addr = start();
+        let silent: Vec<_> = (0..MAX_SESSIONS)
+            .map(|_| {
+                let stream = TcpStream::connect(addr).unwrap();
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .unwrap();
+                stream
+            })
+            .collect();
+        // Let the server take both before trying a third.
+        thread::sleep(LOGIN_TIMEOUT / 5);
+        assert_eq!(
+            transcript_raw(addr, b""),
+            b"Too many sessions, try again later.\r\n"
+        );
+
+        for mut stream in silent {
+            let mut output = Vec::new();
+            stream.read_to_end(&mut output).unwrap();
+            assert!(output.ends_with(b"Password: \r\nTimed out.\r\n"));
+        }
+        // The slots are released right after the connection closes.
+        let mut output = transcript_raw(addr, b"secret\rexit\r");
+        for _ in 0..20 {
+            if !output.starts_with(b"Too many") {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+            output = transcript_raw(addr, b"secret\rexit\r");
+        }
+        assert!(output.ends_with(b"Bye.\r\n"), "{:?}", output);
+    }
+
+    #[test]
+    fn idle_sessions_time_out() {
+        let addr = start();
+        let output = transcript(addr, b"secret\r");
+        assert!(output.ends_with("esp> \r\nTimed out.\r\n"), "{:?}", output);
+    }
+
+    #[test]
+    fn wrong_password_gets_no_prompt() {
+        let addr = start();
+        let output = transcript(addr, &[b"wrong\r\n".as_slice(), &[CTRL_D]].concat());
+        assert_eq!(
+            output,
+            "Password: \r\nWrong password.\r\nPassword: Login failed.\r\n"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! # storage
+//! SPIFFS filesystem on the `storage` flash partition, mounted through the ESP-IDF VFS.
+//!
+//! The partition is declared in `partitions.csv` (flashed by espflash, see
+//! `espflash.toml`). It is formatted automatically
+//! the first time it fails to mount (e.g. on a freshly flashed board).
+//!
+
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use esp_idf_sys::{esp, esp_spiffs_info, esp_vfs_spiffs_conf_t, esp_vfs_spiffs_register};
+use log::info;
+
+/// Where the filesystem appears in the VFS (`std::fs` paths start here).
+pub const FS_MOUNT_POINT: &str = "/spiffs";
+
+const MOUNT_POINT_C: &CStr = c"/spiffs";
+const PARTITION_LABEL_C: &CStr = c"storage";
+const MAX_OPEN_FILES: usize = 8;
+
+static MOUNTED: AtomicBool = AtomicBool::new(false);
+
+/// Total and used bytes of the mounted filesystem.
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    pub total: usize,
+    pub used: usize,
+}
+
+/// Registers the SPIFFS partition at [`FS_MOUNT_POINT`]. Safe to call more than once.
+pub fn mount() -> Result<Usage> {
+    if !MOUNTED.swap(true, Ordering::SeqCst) {
+        let conf = esp_vfs_spiffs_conf_t {
+            base_path: MOUNT_POINT_C.as_ptr(),
+            partition_label: PARTITION_LABEL_C.as_ptr(),
+            max_files: MAX_OPEN_FILES,
+            format_if_mount_failed: true,
+        };
+        if let Err(e) = esp!(unsafe { esp_vfs_spiffs_register(&conf) }) {
+            MOUNTED.store(false, Ordering::SeqCst);
+            return Err(e.into());
+        }
+    }
+
+    let usage = usage()?;
+    info!(
+        "SPIFFS mounted at {}: {} of {} bytes used",
+        FS_MOUNT_POINT, usage.used, usage.total
+    );
+    Ok(usage)
+}
+
+/// Queries total/used size of the SPIFFS partition.
+pub fn usage() -> Result<Usage> {
+    let mut total = 0;
+    let mut used = 0;
+    esp!(unsafe { esp_spiffs_info(PARTITION_LABEL_C.as_ptr(), &mut total, &mut used) })?;
+    Ok(Usage { total, used })
+}
@@ -0,0 +1,195 @@
+//! # system
+//! Chip, task, heap and reset information gathered from ESP-IDF/FreeRTOS.
+//!
+
+use std::ffi::CStr;
+use std::time::Duration;
+
+use esp_idf_sys as sys;
+
+/// One FreeRTOS task as reported by `uxTaskGetSystemState`.
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub number: sys::UBaseType_t,
+    pub name: String,
+    pub state: &'static str,
+    pub priority: sys::UBaseType_t,
+    /// Minimum free stack seen so far, in bytes.
+    pub stack_high_water: u32,
+    /// Accumulated run time in run-time-stats ticks. FreeRTOS keeps it in 32 bits,
+    /// so it wraps (every ~71 minutes with the default 1 MHz counter).
+    pub run_time: u64,
+}
+
+/// All tasks plus the total run time used to compute CPU shares.
+#[derive(Debug, Clone)]
+pub struct TaskSnapshot {
+    pub tasks: Vec<TaskInfo>,
+    pub total_run_time: u64,
+}
+
+/// Reads the state of every task. Needs `CONFIG_FREERTOS_USE_TRACE_FACILITY`
+/// (and `CONFIG_FREERTOS_GENERATE_RUN_TIME_STATS` for run times).
+pub fn tasks() -> TaskSnapshot {
+    // Leave room for tasks created between counting and reading.
+    let capacity = unsafe { sys::uxTaskGetNumberOfTasks() } as usize + 4;
+    let mut raw: Vec<sys::TaskStatus_t> = Vec::with_capacity(capacity);
+    let mut total_run_time = 0;
+    let count =
+        unsafe { sys::uxTaskGetSystemState(raw.as_mut_ptr(), capacity as _, &mut total_run_time) };
+    unsafe { raw.set_len(count as usize) };
+
+    let tasks = raw
+        .iter()
+        .map(|t| TaskInfo {
+            number: t.xTaskNumber,
+            name: unsafe { CStr::from_ptr(t.pcTaskName) }
+                .to_string_lossy()
+                .into_owned(),
+            state: task_state_name(t.eCurrentState),
+            priority: t.uxCurrentPriority,
+            stack_high_water: t.usStackHighWaterMark,
+            run_time: u64::from(t.ulRunTimeCounter),
+        })
+        .collect();
+
+    TaskSnapshot {
+        tasks,
+        total_run_time: u64::from(total_run_time),
+    }
+}
+
+fn task_state_name(state: sys::eTaskState) -> &'static str {
+    match state {
+        sys::eTaskState_eRunning => "run",
+        sys::eTaskState_eReady => "ready",
+        sys::eTaskState_eBlocked => "block",
+        sys::eTaskState_eSuspended => "susp",
+        sys::eTaskState_eDeleted => "del",
+        _ => "?",
+    }
+}
+
+/// Heap statistics for one set of `MALLOC_CAP_*` capabilities.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    pub total: usize,
+    pub free: usize,
+    pub largest_block: usize,
+    pub min_free_ever: usize,
+}
+
+pub fn heap_stats(caps: u32) -> HeapStats {
+    unsafe {
+        HeapStats {
+            total: sys::heap_caps_get_total_size(caps),
+            free: sys::heap_caps_get_free_size(caps),
+            largest_block: sys::heap_caps_get_largest_free_block(caps),
+            min_free_ever: sys::heap_caps_get_minimum_free_size(caps),
+        }
+    }
+}
+
+/// Capability sets shown by the `heap` command.
+pub const HEAP_CAPS: &[(&str, u32)] = &[
+    ("8bit", sys::MALLOC_CAP_8BIT),
+    ("32bit", sys::MALLOC_CAP_32BIT),
+    ("dma", sys::MALLOC_CAP_DMA),
+    ("intern", sys::MALLOC_CAP_INTERNAL),
+    ("spiram", sys::MALLOC_CAP_SPIRAM),
+];
+
+/// Time since boot.
+pub fn uptime() -> Duration {
+    Duration::from_micros(unsafe { sys::esp_timer_get_time() } as u64)
+}
+
+/// Formats a duration as `[Nd ]HH:MM:SS`.
+pub fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    let hms = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}d {}", days, hms)
+    } else {
+        hms
+    }
+}
+
+/// Why the chip last reset, as a short name.
+pub fn reset_reason() -> &'static str {
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => "power-on",
+        sys::esp_reset_reason_t_ESP_RST_EXT => "external pin",
+        sys::esp_reset_reason_t_ESP_RST_SW => "software (esp_restart)",
+        sys::esp_reset_reason_t_ESP_RST_PANIC => "panic",
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT => "interrupt watchdog",
+        sys::esp_reset_reason_t_ESP_RST_TASK_WDT => "task watchdog",
+        sys::esp_reset_reason_t_ESP_RST_WDT => "other watchdog",
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => "deep sleep wake",
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => "brownout",
+        sys::esp_reset_reason_t_ESP_RST_SDIO => "SDIO",
+        _ => "unknown",
+    }
+}
+
+/// Whether the last reset was a panic or watchdog, rather than power-on, reset
+/// button, `esp_restart` or deep sleep.
+pub fn reset_was_crash() -> bool {
+    matches!(
+        unsafe { sys::esp_reset_reason() },
+        sys::esp_reset_reason_t_ESP_RST_PANIC
+            | sys::esp_reset_reason_t_ESP_RST_INT_WDT
+            | sys::esp_reset_reason_t_ESP_RST_TASK_WDT
+            | sys::esp_reset_reason_t_ESP_RST_WDT
+    )
+}
+
+/// Static facts about the chip.
+#[derive(Debug, Clone)]
+pub struct ChipInfo {
+    pub model: &'static str,
+    pub revision: u16,
+    pub cores: u8,
+    pub features: Vec<&'static str>,
+    pub flash_size: u32,
+    pub mac: [u8; 6],
+}
+
+pub fn chip_info() -> ChipInfo {
+    let mut info = sys::esp_chip_info_t::default();
+    unsafe { sys::esp_chip_info(&mut info) };
+
+    let model = match info.model {
+        sys::esp_chip_model_t_CHIP_ESP32 => "ESP32",
+        sys::esp_chip_model_t_CHIP_ESP32S2 => "ESP32-S2",
+        sys::esp_chip_model_t_CHIP_ESP32S3 => "ESP32-S3",
+        sys::esp_chip_model_t_CHIP_ESP32C3 => "ESP32-C3",
+        _ => "ESP32 (other)",
+    };
+    let features = [
+        (sys::CHIP_FEATURE_EMB_FLASH, "emb-flash"),
+        (sys::CHIP_FEATURE_WIFI_BGN, "wifi"),
+        (sys::CHIP_FEATURE_BLE, "ble"),
+        (sys::CHIP_FEATURE_BT, "bt"),
+        (sys::CHIP_FEATURE_EMB_PSRAM, "emb-psram"),
+    ]
+    .into_iter()
+    .filter(|(bit, _)| info.features & bit != 0)
+    .map(|(_, name)| name)
+    .collect();
+
+    let mut flash_size = 0;
+    unsafe { sys::esp_flash_get_size(std::ptr::null_mut(), &mut flash_size) };
+    let mut mac = [0u8; 6];
+    unsafe { sys::esp_efuse_mac_get_default(mac.as_mut_ptr()) };
+
+    ChipInfo {
+        model,
+        revision: info.revision,
+        cores: info.cores,
+        features,
+        flash_size,
+        mac,
+    }
+}
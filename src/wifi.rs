@@ -0,0 +1,116 @@
+//! # wifi
+//! Wi-Fi station bring-up with credentials stored in NVS.
+//!
+//! Store them once from the shell, e.g. `nvs set str wifi ssid MyNetwork` and
+//! `nvs set str wifi pass secret` (leave `pass` unset for open networks).
+//!
+//! [`connect`] joins once; services that must stay reachable then call
+//! [`stay_connected`] to rejoin whenever the station drops.
+//!
+
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use esp_idf_hal::modem::Modem;
+use esp_idf_svc::{
+    eventloop::EspSystemEventLoop,
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    wifi::{AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi},
+};
+use log::{info, warn};
+
+/// NVS namespace holding the station credentials.
+pub const NVS_NAMESPACE: &str = "wifi";
+pub const NVS_SSID_KEY: &str = "ssid";
+pub const NVS_PASS_KEY: &str = "pass";
+
+/// How often [`stay_connected`] checks the station.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Network to join as a station.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub ssid: String,
+    pub password: String,
+}
+
+/// Reads the credentials from NVS; `None` if no SSID is stored.
+pub fn load_credentials(partition: &EspDefaultNvsPartition) -> Result<Option<Credentials>> {
+    let nvs = EspNvs::new(partition.clone(), NVS_NAMESPACE, true)?;
+    let Some(ssid) = read_str(&nvs, NVS_SSID_KEY)? else {
+        return Ok(None);
+    };
+    let password = read_str(&nvs, NVS_PASS_KEY)?.unwrap_or_default();
+    Ok(Some(Credentials { ssid, password }))
+}
+
+/// Reads a string value of any length.
+pub fn read_str(nvs: &EspNvs<NvsDefault>, key: &str) -> Result<Option<String>> {
+    let Some(len) = nvs.str_len(key)? else {
+        return Ok(None);
+    };
+    let mut buf = vec![0u8; len];
+    Ok(nvs.get_str(key, &mut buf)?.map(str::to_string))
+}
+
+/// Joins the network and waits until the station has an IP address.
+/// The connection stays up as long as the returned driver lives.
+pub fn connect(
+    modem: Modem,
+    sysloop: EspSystemEventLoop,
+    nvs: EspDefaultNvsPartition,
+    credentials: &Credentials,
+) -> Result<BlockingWifi<EspWifi<'static>>> {
+    let mut wifi = BlockingWifi::wrap(EspWifi::new(modem, sysloop.clone(), Some(nvs))?, sysloop)?;
+
+    let auth_method = if credentials.password.is_empty() {
+        AuthMethod::None
+    } else {
+        AuthMethod::WPA2Personal
+    };
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: credentials
+            .ssid
+            .as_str()
+            .try_into()
+            .map_err(|_| anyhow!("SSID too long: {}", credentials.ssid))?,
+        password: credentials
+            .password
+            .as_str()
+            .try_into()
+            .map_err(|_| anyhow!("Wi-Fi password too long"))?,
+        auth_method,
+        ..Default::default()
+    }))?;
+
+    info!("Connecting to Wi-Fi network {}", credentials.ssid);
+    wifi.start()?;
+    wifi.connect()?;
+    wifi.wait_netif_up()?;
+
+    let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
+    info!("Wi-Fi connected, IP {}", ip_info.ip);
+    Ok(wifi)
+}
+
+/// Checks the station every [`CHECK_INTERVAL`], forever, and joins the network
+/// again when it has dropped (access point restarted, out of range). A failed
+/// attempt is retried at the next check.
+pub fn stay_connected(wifi: &mut BlockingWifi<EspWifi<'static>>) {
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+        match wifi.is_connected() {
+            Ok(true) => continue,
+            Ok(false) => warn!("Wi-Fi connection lost, reconnecting"),
+            Err(e) => {
+                warn!("Wi-Fi status unknown: {}", e);
+                continue;
+            }
+        }
+        match wifi.connect().and_then(|_| wifi.wait_netif_up()) {
+            Ok(()) => info!("Wi-Fi reconnected"),
+            Err(e) => warn!("Wi-Fi reconnect failed: {}", e),
+        }
+    }
+}